@@ -4,69 +4,552 @@ use arcis_imports::*;
 mod circuits {
     use arcis_imports::*;
 
-    /// Bidder public key has been split into two u128s(Arcis encrypts)
+    /// Number of ranked bids kept in the encrypted state. Single-lot auctions
+    /// only read the top two; multi-unit auctions have at most this many winners.
+    /// This is the sizing parameter for multi-unit sales: raising it grows the
+    /// state, the work per bid and the revealed resolution output.
+    const MAX_WINNERS: usize = 16;
+
+    /// Tie-break policies, matching the discriminants of the program's `TieBreak`.
+    const TIE_BREAK_EARLIEST_BID: u8 = 0;
+    const TIE_BREAK_PRO_RATA: u8 = 1;
+    const TIE_BREAK_RANDOM: u8 = 2;
+
+    /// `amount` is the price per unit and `quantity` the number of units wanted.
+    /// `max_spend` caps the total cost of the bid; zero means no cap.
     pub struct Bid {
-        pub bidder_lo: u128,
-        pub bidder_hi: u128,
         pub amount: u64,
+        pub quantity: u64,
+        pub max_spend: u64,
     }
+    /// The best bids seen so far, ordered by price with the highest first.
+    /// Empty slots have a zero amount.
     pub struct AuctionState{
-            pub highest_bid: u64,
-            pub highest_bidder_lo: u128,
-            pub highest_bidder_hi: u128,
-            pub second_highest_bid: u64,
-            pub bid_count: u8,
+            pub bidders_lo: [u128; MAX_WINNERS],
+            pub bidders_hi: [u128; MAX_WINNERS],
+            pub amounts: [u64; MAX_WINNERS],
+            pub quantities: [u64; MAX_WINNERS],
     }
+    /// `price_shared` is set when another bid matched the winning price.
     pub struct AuctionResult{
         pub winner_lo: u128,
         pub winner_hi: u128,
         pub payment_amount: u64,
+        pub price_shared: bool,
+    }
+    /// Only bidders that actually receive units are revealed; every other slot is zeroed.
+    pub struct UniformPriceResult {
+        pub clearing_price: u64,
+        pub winners_lo: [u128; MAX_WINNERS],
+        pub winners_hi: [u128; MAX_WINNERS],
+        pub quantities: [u64; MAX_WINNERS],
+        pub price_shared: bool,
+    }
+    /// Same as `UniformPriceResult`, except every winner pays their own price.
+    pub struct DiscriminatoryResult {
+        pub winners_lo: [u128; MAX_WINNERS],
+        pub winners_hi: [u128; MAX_WINNERS],
+        pub quantities: [u64; MAX_WINNERS],
+        pub prices: [u64; MAX_WINNERS],
+        pub price_shared: bool,
+    }
+    /// What one bidder privately learns about their own bid: whether they won,
+    /// the units and per-unit price they were allocated, and their bid as the
+    /// auction recorded it (zero if it was rejected or fell outside the ranking).
+    pub struct BidderResult {
+        pub won: bool,
+        pub quantity: u64,
+        pub price: u64,
+        pub bid_amount: u64,
+        pub bid_quantity: u64,
+    }
+
+    /// Picks the single-lot winner among the bids tied at the top price. The
+    /// ranking keeps equal prices in arrival order, so slot 0 is the earliest.
+    /// `Random` draws uniformly among the tied slots.
+    fn single_lot_winner(tie_break: u8, amounts: [u64; MAX_WINNERS]) -> (u64, bool) {
+        let mut winner: u64 = 0;
+        let mut tied_bids: u64 = 1;
+        for i in 1..MAX_WINNERS {
+            if amounts[0] > 0 && amounts[i] == amounts[0] {
+                tied_bids += 1;
+                let draw = ArcisRNG::gen_integer_from_width(64) % (tied_bids as u128);
+                if tie_break == TIE_BREAK_RANDOM && draw == 0 {
+                    winner = i as u64;
+                }
+            }
+        }
+        (winner, tied_bids > 1)
+    }
+
+    /// Splits `total_units` across the ranked bids. Every bid priced above the
+    /// marginal price (the lowest price that still gets units) is filled in full;
+    /// the units left at the marginal price go to the bids tied there according
+    /// to `tie_break`:
+    /// - earliest bid: in arrival order,
+    /// - pro-rata: in proportion to quantity, leftover units one each in arrival order,
+    /// - random: in a random order drawn inside the MPC.
+    fn allocate_units(
+        tie_break: u8,
+        total_units: u64,
+        amounts: [u64; MAX_WINNERS],
+        quantities: [u64; MAX_WINNERS],
+    ) -> ([u64; MAX_WINNERS], u64, bool) {
+        let mut remaining = total_units;
+        let mut marginal_price: u64 = 0;
+        for i in 0..MAX_WINNERS {
+            let mut take = quantities[i];
+            if take > remaining {
+                take = remaining;
+            }
+            if take > 0 {
+                marginal_price = amounts[i];
+            }
+            remaining -= take;
+        }
+
+        let mut filled_above: u64 = 0;
+        let mut tied_demand: u64 = 0;
+        let mut tied_bids: u64 = 0;
+        let mut tied = [false; MAX_WINNERS];
+        let mut allocated = [0u64; MAX_WINNERS];
+        for i in 0..MAX_WINNERS {
+            if amounts[i] > marginal_price {
+                allocated[i] = quantities[i];
+                filled_above += quantities[i];
+            } else if marginal_price > 0 && amounts[i] == marginal_price {
+                tied[i] = true;
+                tied_demand += quantities[i];
+                tied_bids += 1;
+            }
+        }
+        let available = total_units - filled_above;
+
+        let mut earliest = [0u64; MAX_WINNERS];
+        let mut left = available;
+        for i in 0..MAX_WINNERS {
+            if tied[i] {
+                let mut take = quantities[i];
+                if take > left {
+                    take = left;
+                }
+                earliest[i] = take;
+                left -= take;
+            }
+        }
+
+        let mut pro_rata = [0u64; MAX_WINNERS];
+        let mut demand = tied_demand;
+        if demand == 0 {
+            demand = 1;
+        }
+        let mut left = available;
+        for i in 0..MAX_WINNERS {
+            if tied[i] {
+                let mut share = quantities[i];
+                if tied_demand > available {
+                    share = ((quantities[i] as u128) * (available as u128) / (demand as u128)) as u64;
+                }
+                pro_rata[i] = share;
+                left -= share;
+            }
+        }
+        for i in 0..MAX_WINNERS {
+            if tied[i] && left > 0 && pro_rata[i] < quantities[i] {
+                pro_rata[i] += 1;
+                left -= 1;
+            }
+        }
+
+        // Serve the tied bids highest random key first.
+        let mut keys = [0u128; MAX_WINNERS];
+        for key in keys.iter_mut() {
+            *key = ArcisRNG::gen_integer_from_width(64);
+        }
+        let mut random = [0u64; MAX_WINNERS];
+        let mut served = [false; MAX_WINNERS];
+        let mut left = available;
+        for _ in 0..MAX_WINNERS {
+            let mut found = false;
+            let mut next: u64 = 0;
+            let mut next_key: u128 = 0;
+            for i in 0..MAX_WINNERS {
+                if tied[i] && !served[i] && (!found || keys[i] > next_key) {
+                    found = true;
+                    next = i as u64;
+                    next_key = keys[i];
+                }
+            }
+            for i in 0..MAX_WINNERS {
+                if found && next == i as u64 {
+                    let mut take = quantities[i];
+                    if take > left {
+                        take = left;
+                    }
+                    random[i] = take;
+                    served[i] = true;
+                    left -= take;
+                }
+            }
+        }
+
+        for i in 0..MAX_WINNERS {
+            if tied[i] {
+                if tie_break == TIE_BREAK_EARLIEST_BID {
+                    allocated[i] = earliest[i];
+                }
+                if tie_break == TIE_BREAK_PRO_RATA {
+                    allocated[i] = pro_rata[i];
+                }
+                if tie_break == TIE_BREAK_RANDOM {
+                    allocated[i] = random[i];
+                }
+            }
+        }
+        (allocated, marginal_price, tied_bids > 1)
+    }
+
+    /// Drops the signer's bid from the ranking and moves every lower bid up one
+    /// slot. A signer holds at most one slot, since every new bid replaces the last.
+    /// A bid pushed out of the ranking earlier does not come back into the freed
+    /// slot; the program has the ranking rebuilt with `rerank_bids` instead.
+    fn remove_bidder(state: AuctionState, signer_lo: u128, signer_hi: u128) -> AuctionState {
+        let mut state = state;
+        let mut shift = false;
+        for i in 0..MAX_WINNERS {
+            if state.amounts[i] > 0 && state.bidders_lo[i] == signer_lo && state.bidders_hi[i] == signer_hi {
+                shift = true;
+            }
+            if shift {
+                if i + 1 < MAX_WINNERS {
+                    state.bidders_lo[i] = state.bidders_lo[i + 1];
+                    state.bidders_hi[i] = state.bidders_hi[i + 1];
+                    state.amounts[i] = state.amounts[i + 1];
+                    state.quantities[i] = state.quantities[i + 1];
+                } else {
+                    state.bidders_lo[i] = 0;
+                    state.bidders_hi[i] = 0;
+                    state.amounts[i] = 0;
+                    state.quantities[i] = 0;
+                }
+            }
+        }
+        state
+    }
+
+    fn empty_state() -> AuctionState {
+        AuctionState {
+            bidders_lo: [0; MAX_WINNERS],
+            bidders_hi: [0; MAX_WINNERS],
+            amounts: [0; MAX_WINNERS],
+            quantities: [0; MAX_WINNERS],
+        }
     }
 
     #[instruction]
     pub fn init_auction_state(mxe: Mxe) -> Enc<Mxe, AuctionState> {
-        let initial_state = AuctionState {
-            highest_bid: 0,
-            highest_bidder_lo: 0,
-            highest_bidder_hi: 0,
-            second_highest_bid: 0,
-            bid_count: 0,
-        };
-        mxe.from_arcis(initial_state)
+        mxe.from_arcis(empty_state())
     }
 
+    /// One slot of a `process_bids` batch: the queued bid and the plaintext
+    /// limits the program attached to it. Slots past the queued bids are not
+    /// `present` and change nothing.
+    struct BatchSlot {
+        bid: Bid,
+        present: bool,
+        signer_lo: u128,
+        signer_hi: u128,
+        deposit: u64,
+        max_units: u64,
+    }
+
+    /// Folds one queued bid into the ranking. Bids below `min_bid`, or costing more
+    /// than the `deposit` escrowed with them, are dropped. The quantity is first
+    /// trimmed to `max_units` (the supply, or the bidder's allowlist cap if lower),
+    /// then to the tighter of the bid's own spend cap and the auction's
+    /// `max_bid_spend`. A price above the spend cap leaves no units and drops the
+    /// bid. The bidder is the signer of `place_bid`, passed in by the program as
+    /// two u128 halves, and a new bid replaces any earlier one from the same signer.
+    fn fold_bid(state: AuctionState, slot: BatchSlot, min_bid: u64, max_bid_spend: u64) -> AuctionState {
+        let BatchSlot {
+            bid,
+            present,
+            signer_lo,
+            signer_hi,
+            deposit,
+            max_units,
+        } = slot;
+        let mut state = remove_bidder(state, signer_lo, signer_hi);
+
+        let mut quantity = bid.quantity;
+        if quantity > max_units {
+            quantity = max_units;
+        }
+        // A spend cap buys as many whole units as it can afford at the bid price.
+        // Zero means no cap, on either side.
+        let mut spend_cap = bid.max_spend;
+        if spend_cap == 0 || (max_bid_spend > 0 && max_bid_spend < spend_cap) {
+            spend_cap = max_bid_spend;
+        }
+        if spend_cap > 0 && bid.amount > 0 {
+            let affordable = spend_cap / bid.amount;
+            if quantity > affordable {
+                quantity = affordable;
+            }
+        }
+        let cost = (bid.amount as u128) * (quantity as u128);
+
+        // A rejected bid is carried as an empty slot, which never displaces anything.
+        let mut bidder_lo: u128 = 0;
+        let mut bidder_hi: u128 = 0;
+        let mut amount: u64 = 0;
+        let mut units: u64 = 0;
+        if present && bid.amount >= min_bid && quantity > 0 && cost <= (deposit as u128) {
+            bidder_lo = signer_lo;
+            bidder_hi = signer_hi;
+            amount = bid.amount;
+            units = quantity;
+        }
+
+        // Carry the new bid down the ranking, swapping it with every lower-priced
+        // entry it passes. Equal prices keep the earlier bid ahead.
+        for i in 0..MAX_WINNERS {
+            if amount > state.amounts[i] {
+                let prev_lo = state.bidders_lo[i];
+                let prev_hi = state.bidders_hi[i];
+                let prev_amount = state.amounts[i];
+                let prev_units = state.quantities[i];
+                state.bidders_lo[i] = bidder_lo;
+                state.bidders_hi[i] = bidder_hi;
+                state.amounts[i] = amount;
+                state.quantities[i] = units;
+                bidder_lo = prev_lo;
+                bidder_hi = prev_hi;
+                amount = prev_amount;
+                units = prev_units;
+            }
+        }
+        state
+    }
+
+    /// Folds up to four queued bids, in queue order, into the auction state. The
+    /// batch size matches the program's `BID_BATCH_SIZE`; only the first `count`
+    /// slots hold real bids. The output state is re-encrypted either way, so nobody
+    /// can tell a rejected or trimmed bid from an accepted one. With `reset` the
+    /// batch starts from an empty ranking, which is how the program's
+    /// `rerank_bids` rebuilds it from every active bid.
+    // Circuit inputs are positional and must line up with the `ArgBuilder` calls
+    // in the program's `process_bids`, so each slot stays a run of plain arguments.
+    #[allow(clippy::too_many_arguments)]
     #[instruction]
-    pub fn place_bid(bid_ctx: Enc<Mxe, Bid>,state_ctxt: Enc<Mxe, AuctionState>) -> Enc<Mxe, AuctionState> {
-        let bid = bid_ctx.to_arcis();
+    pub fn process_bids(
+        bid_0: Enc<Shared, Bid>,
+        signer_lo_0: u128,
+        signer_hi_0: u128,
+        deposit_0: u64,
+        max_units_0: u64,
+        bid_1: Enc<Shared, Bid>,
+        signer_lo_1: u128,
+        signer_hi_1: u128,
+        deposit_1: u64,
+        max_units_1: u64,
+        bid_2: Enc<Shared, Bid>,
+        signer_lo_2: u128,
+        signer_hi_2: u128,
+        deposit_2: u64,
+        max_units_2: u64,
+        bid_3: Enc<Shared, Bid>,
+        signer_lo_3: u128,
+        signer_hi_3: u128,
+        deposit_3: u64,
+        max_units_3: u64,
+        count: u8,
+        min_bid: u64,
+        max_bid_spend: u64,
+        reset: bool,
+        state_ctxt: Enc<Mxe, AuctionState>,
+    ) -> Enc<Mxe, AuctionState> {
         let mut state = state_ctxt.to_arcis();
-        if bid.amount > state.highest_bid {
-            state.second_highest_bid = state.highest_bid;
-            state.highest_bid = bid.amount;
-            state.highest_bidder_lo = bid.bidder_lo;
-            state.highest_bidder_hi = bid.bidder_hi;
-        } else if bid.amount > state.second_highest_bid {
-            state.second_highest_bid = bid.amount;
-        }
-        state.bid_count += 1;
+        if reset {
+            state = empty_state();
+        }
+        let slot_0 = BatchSlot {
+            bid: bid_0.to_arcis(),
+            present: count > 0,
+            signer_lo: signer_lo_0,
+            signer_hi: signer_hi_0,
+            deposit: deposit_0,
+            max_units: max_units_0,
+        };
+        state = fold_bid(state, slot_0, min_bid, max_bid_spend);
+        let slot_1 = BatchSlot {
+            bid: bid_1.to_arcis(),
+            present: count > 1,
+            signer_lo: signer_lo_1,
+            signer_hi: signer_hi_1,
+            deposit: deposit_1,
+            max_units: max_units_1,
+        };
+        state = fold_bid(state, slot_1, min_bid, max_bid_spend);
+        let slot_2 = BatchSlot {
+            bid: bid_2.to_arcis(),
+            present: count > 2,
+            signer_lo: signer_lo_2,
+            signer_hi: signer_hi_2,
+            deposit: deposit_2,
+            max_units: max_units_2,
+        };
+        state = fold_bid(state, slot_2, min_bid, max_bid_spend);
+        let slot_3 = BatchSlot {
+            bid: bid_3.to_arcis(),
+            present: count > 3,
+            signer_lo: signer_lo_3,
+            signer_hi: signer_hi_3,
+            deposit: deposit_3,
+            max_units: max_units_3,
+        };
+        state = fold_bid(state, slot_3, min_bid, max_bid_spend);
         state_ctxt.owner.from_arcis(state)
     }
     #[instruction]
-    pub fn first_winner(state_ctxt: Enc<Mxe, AuctionState>) -> AuctionResult {
+    pub fn cancel_bid(signer_lo: u128, signer_hi: u128, state_ctxt: Enc<Mxe, AuctionState>) -> Enc<Mxe, AuctionState> {
+        let state = remove_bidder(state_ctxt.to_arcis(), signer_lo, signer_hi);
+        state_ctxt.owner.from_arcis(state)
+    }
+    #[instruction]
+    pub fn first_winner(tie_break: u8, state_ctxt: Enc<Mxe, AuctionState>) -> AuctionResult {
         let state = state_ctxt.to_arcis();
+        let (winner, price_shared) = single_lot_winner(tie_break, state.amounts);
+        let mut winner_lo: u128 = 0;
+        let mut winner_hi: u128 = 0;
+        for i in 0..MAX_WINNERS {
+            if winner == i as u64 {
+                winner_lo = state.bidders_lo[i];
+                winner_hi = state.bidders_hi[i];
+            }
+        }
         AuctionResult {
-            winner_lo: state.highest_bidder_lo,
-            winner_hi: state.highest_bidder_hi,
-            payment_amount: state.highest_bid,
+            winner_lo,
+            winner_hi,
+            payment_amount: state.amounts[0],
+            price_shared,
         }.reveal()
         
     }
+    /// With a single valid bid there is no second price, so the payment is
+    /// floored at the reserve instead of dropping to zero. On a tie at the top
+    /// the second price equals the winning price.
     #[instruction]
-    pub fn second_winner(state_ctxt: Enc<Mxe, AuctionState>) -> AuctionResult {
+    pub fn second_winner(tie_break: u8, min_bid: u64, state_ctxt: Enc<Mxe, AuctionState>) -> AuctionResult {
         let state = state_ctxt.to_arcis();
+        let (winner, price_shared) = single_lot_winner(tie_break, state.amounts);
+        let mut winner_lo: u128 = 0;
+        let mut winner_hi: u128 = 0;
+        for i in 0..MAX_WINNERS {
+            if winner == i as u64 {
+                winner_lo = state.bidders_lo[i];
+                winner_hi = state.bidders_hi[i];
+            }
+        }
+        let mut payment_amount = state.amounts[1];
+        if state.amounts[0] > 0 && payment_amount < min_bid {
+            payment_amount = min_bid;
+        }
         AuctionResult {
-            winner_lo: state.highest_bidder_lo,
-            winner_hi: state.highest_bidder_hi,
-            payment_amount: state.second_highest_bid,
+            winner_lo,
+            winner_hi,
+            payment_amount,
+            price_shared,
         }.reveal()
     }
+    /// Fills `total_units` from the top of the ranking down. Every winner pays the
+    /// clearing price, which is the lowest price that still received units.
+    #[instruction]
+    pub fn uniform_price_winners(tie_break: u8, total_units: u64, state_ctxt: Enc<Mxe, AuctionState>) -> UniformPriceResult {
+        let state = state_ctxt.to_arcis();
+        let (allocated, clearing_price, price_shared) =
+            allocate_units(tie_break, total_units, state.amounts, state.quantities);
+        let mut winners_lo = [0u128; MAX_WINNERS];
+        let mut winners_hi = [0u128; MAX_WINNERS];
+        let mut quantities = [0u64; MAX_WINNERS];
+        for i in 0..MAX_WINNERS {
+            if allocated[i] > 0 {
+                winners_lo[i] = state.bidders_lo[i];
+                winners_hi[i] = state.bidders_hi[i];
+                quantities[i] = allocated[i];
+            }
+        }
+        UniformPriceResult {
+            clearing_price,
+            winners_lo,
+            winners_hi,
+            quantities,
+            price_shared,
+        }.reveal()
+    }
+    /// Fills `total_units` from the top of the ranking down, and every winner
+    /// pays the price they bid (pay-as-bid).
+    #[instruction]
+    pub fn discriminatory_winners(tie_break: u8, total_units: u64, state_ctxt: Enc<Mxe, AuctionState>) -> DiscriminatoryResult {
+        let state = state_ctxt.to_arcis();
+        let (allocated, _, price_shared) =
+            allocate_units(tie_break, total_units, state.amounts, state.quantities);
+        let mut winners_lo = [0u128; MAX_WINNERS];
+        let mut winners_hi = [0u128; MAX_WINNERS];
+        let mut quantities = [0u64; MAX_WINNERS];
+        let mut prices = [0u64; MAX_WINNERS];
+        for i in 0..MAX_WINNERS {
+            if allocated[i] > 0 {
+                winners_lo[i] = state.bidders_lo[i];
+                winners_hi[i] = state.bidders_hi[i];
+                quantities[i] = allocated[i];
+                prices[i] = state.amounts[i];
+            }
+        }
+        DiscriminatoryResult {
+            winners_lo,
+            winners_hi,
+            quantities,
+            prices,
+            price_shared,
+        }.reveal()
+    }
+    /// The allocation is read back from the resolved auction, so random
+    /// tie-breaks are not drawn again. `allocated` and `unit_price` are public
+    /// plaintext taken from `AuctionAllocations`, which already lists every
+    /// winner; only the recorded bid amount and quantity come from the
+    /// encrypted state and stay private to the bidder.
+    #[instruction]
+    pub fn bidder_result(
+        bidder: Shared,
+        signer_lo: u128,
+        signer_hi: u128,
+        allocated: u64,
+        unit_price: u64,
+        state_ctxt: Enc<Mxe, AuctionState>,
+    ) -> Enc<Shared, BidderResult> {
+        let state = state_ctxt.to_arcis();
+        let mut found = false;
+        let mut bid_amount: u64 = 0;
+        let mut bid_quantity: u64 = 0;
+        for i in 0..MAX_WINNERS {
+            if !found
+                && state.amounts[i] > 0
+                && state.bidders_lo[i] == signer_lo
+                && state.bidders_hi[i] == signer_hi
+            {
+                found = true;
+                bid_amount = state.amounts[i];
+                bid_quantity = state.quantities[i];
+            }
+        }
+        let result = BidderResult {
+            won: allocated > 0,
+            quantity: allocated,
+            price: unit_price,
+            bid_amount,
+            bid_quantity,
+        };
+        bidder.from_arcis(result)
+    }
 }
@@ -1,22 +1,88 @@
 use anchor_lang::prelude::*;
+use anchor_spl::token_interface::{self, Mint, TokenAccount, TokenInterface, TransferChecked};
 use arcium_anchor::prelude::*;
 use arcium_client::idl::arcium::types::CallbackAccount;
+use solana_sha256_hasher::hashv;
 
 const COMP_DEF_OFFSET_INIT_AUCTION_STATE: u32 = comp_def_offset("init_auction_state");
-const COMP_DEF_OFFSET_PLACE_BID: u32 = comp_def_offset("place_bid");
+const COMP_DEF_OFFSET_PROCESS_BIDS: u32 = comp_def_offset("process_bids");
 const COMP_DEF_OFFSET_FIRST_WINNER: u32 = comp_def_offset("first_winner");
 const COMP_DEF_OFFSET_SECOND_WINNER: u32 = comp_def_offset("second_winner");
+const COMP_DEF_OFFSET_UNIFORM_PRICE_WINNERS: u32 = comp_def_offset("uniform_price_winners");
+const COMP_DEF_OFFSET_DISCRIMINATORY_WINNERS: u32 = comp_def_offset("discriminatory_winners");
+const COMP_DEF_OFFSET_BIDDER_RESULT: u32 = comp_def_offset("bidder_result");
+const COMP_DEF_OFFSET_CANCEL_BID: u32 = comp_def_offset("cancel_bid");
+
+/// Ranked bids kept in the encrypted state, and so the most winners a
+/// multi-unit sale can have. Must match `MAX_WINNERS` in `encrypted-ixs`.
+pub const MAX_WINNERS: usize = 16;
+/// Ciphertexts in the encrypted `AuctionState`: bidder lo/hi, amount and quantity
+/// for every ranked bid.
+pub const ENCRYPTED_STATE_FIELDS: usize = 4 * MAX_WINNERS;
+/// Queued bids folded into the auction state by one `process_bids` computation.
+/// Must match the number of bid slots of `process_bids` in `encrypted-ixs`.
+pub const BID_BATCH_SIZE: usize = 4;
+/// Bids that can wait in the queue before `place_bid` rejects new ones. Each
+/// bidder holds at most one entry, so only distinct bidders can fill it.
+pub const BID_QUEUE_CAPACITY: usize = 32;
+/// Seconds to wait for a callback before giving up on it: a stuck bid batch can
+/// then be expired, and a stuck setup or resolution retried.
+pub const COMPUTATION_TIMEOUT: i64 = 600;
 declare_id!("C2vZo71gwGS4NGB1Kh7GnxuWUuYbJi47V4yARYuHm31U");
 #[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, InitSpace)]
 pub enum AuctionType {
     FirstPrice,
     SecondPrice,
+    UniformPrice,
+    Discriminatory,
+}
+/// How bids at the same price are ordered. Equal bids are always ranked in
+/// arrival order; the policy decides who gets the lot (single-lot auctions) or
+/// the units left at the marginal price (multi-unit auctions).
+/// The discriminants are mirrored by the circuits.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, InitSpace)]
+pub enum TieBreak {
+    EarliestBid,
+    /// Multi-unit only: units are split in proportion to the quantities bid.
+    ProRata,
+    /// Drawn inside the MPC, so no party can predict or steer the outcome.
+    Random,
 }
 #[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, InitSpace)]
 pub enum AuctionStatus {
     Open,
     Closed,
     Resolved,
+    /// Aborted by the authority before resolution; every deposit is refunded.
+    Cancelled,
+}
+/// What an aborted computation was doing. Only `InitAuctionState` has its own
+/// retry instruction; every other kind is retried by calling the instruction
+/// that queued it again.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, InitSpace)]
+pub enum ComputationKind {
+    /// Retried with `retry_init_auction_state`.
+    InitAuctionState,
+    /// The batch stays queued; call `process_bids` again. A `rerank_bids` batch
+    /// is retried the same way.
+    ProcessBids,
+    /// The bid stays in place; the bidder calls `cancel_bid` again.
+    CancelBid,
+    /// Call the same resolve instruction again.
+    Resolve,
+    /// Nothing is recorded; the bidder calls `bidder_result` again.
+    BidderResult,
+}
+/// An auction-wide computation that has been queued and not yet succeeded.
+/// Per-bidder computations are tracked on the bidder's receipt instead.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, InitSpace)]
+pub struct PendingComputation {
+    pub kind: ComputationKind,
+    /// Only a callback from this computation account may apply its result.
+    pub computation_account: Pubkey,
+    pub queued_at: i64,
+    /// The callback reported an aborted computation; the auction waits for a retry.
+    pub failed: bool,
 }
 #[arcium_program]
 pub mod ws_confidential_primary_market_auction {
@@ -25,7 +91,7 @@ pub fn init_auction_state_comp_def(ctx: Context<InitAuctionStateCompDef>) -> Res
     init_comp_def(ctx.accounts, None, None)?;
     Ok(())
 }
-pub fn init_place_bid_comp_def(ctx: Context<InitPlaceBidCompDef>) -> Result<()> {
+pub fn init_process_bids_comp_def(ctx: Context<InitProcessBidsCompDef>) -> Result<()> {
     init_comp_def(ctx.accounts, None, None)?;
     Ok(())
 }
@@ -37,6 +103,22 @@ pub fn init_first_winner_comp_def(ctx: Context<InitFirstWinnerCompDef>) -> Resul
     init_comp_def(ctx.accounts, None, None)?;
     Ok(())
 }
+pub fn init_uniform_price_winners_comp_def(ctx: Context<InitUniformPriceWinnersCompDef>) -> Result<()> {
+    init_comp_def(ctx.accounts, None, None)?;
+    Ok(())
+}
+pub fn init_discriminatory_winners_comp_def(ctx: Context<InitDiscriminatoryWinnersCompDef>) -> Result<()> {
+    init_comp_def(ctx.accounts, None, None)?;
+    Ok(())
+}
+pub fn init_bidder_result_comp_def(ctx: Context<InitBidderResultCompDef>) -> Result<()> {
+    init_comp_def(ctx.accounts, None, None)?;
+    Ok(())
+}
+pub fn init_cancel_bid_comp_def(ctx: Context<InitCancelBidCompDef>) -> Result<()> {
+    init_comp_def(ctx.accounts, None, None)?;
+    Ok(())
+}
 pub fn close_auction(ctx: Context<CloseAuction>) -> Result<()> {
     let auction = &mut ctx.accounts.auction;
     require!(
@@ -52,24 +134,176 @@ pub fn close_auction(ctx: Context<CloseAuction>) -> Result<()> {
 
     Ok(())
 }
+/// Replaces the allowlist root, or removes the allowlist with `None`, while
+/// bidding is open. Bids already placed are not re-checked.
+pub fn set_allowlist_root(
+    ctx: Context<SetAllowlistRoot>,
+    allowlist_root: Option<[u8; 32]>,
+) -> Result<()> {
+    let auction = &mut ctx.accounts.auction;
+    require!(
+        auction.status == AuctionStatus::Open,
+        ErrorCode::AuctionNotOpen
+    );
+    auction.allowlist_root = allowlist_root;
+
+    emit!(AllowlistRootUpdatedEvent {
+        auction: auction.key(),
+        allowlist_root,
+    });
+
+    Ok(())
+}
+/// Aborts the sale before it is resolved. Bidders then withdraw their full
+/// deposit and the authority takes the sale supply back with `claim_proceeds`.
+pub fn cancel_auction(ctx: Context<CancelAuction>) -> Result<()> {
+    let auction = &mut ctx.accounts.auction;
+    require!(
+        matches!(auction.status, AuctionStatus::Open | AuctionStatus::Closed),
+        ErrorCode::AuctionAlreadyFinalized
+    );
+    auction.status = AuctionStatus::Cancelled;
+
+    emit!(AuctionCancelledEvent {
+        auction: auction.key(),
+        bid_count: auction.bid_count,
+    });
+
+    Ok(())
+}
+/// Permissionless close, callable by anyone once `end_time` has passed.
+pub fn close_expired_auction(ctx: Context<CloseExpiredAuction>) -> Result<()> {
+    let auction = &mut ctx.accounts.auction;
+    require!(
+        auction.status == AuctionStatus::Open,
+        ErrorCode::AuctionNotOpen
+    );
+    require!(
+        Clock::get()?.unix_timestamp >= auction.end_time,
+        ErrorCode::AuctionNotEnded
+    );
+    auction.status = AuctionStatus::Closed;
+
+    emit!(AuctionClosedEvent {
+        auction: auction.key(),
+        bid_count: auction.bid_count,
+    });
+
+    Ok(())
+}
 pub fn init_auction_state(
     ctx: Context<InitAuctionState>,
     computation_offset: u64,
+    auction_id: u64,
     auction_type: AuctionType,
     min_bid: u64,
     end_time: i64,
+    sale_amount: u64,
+    total_units: u64,
+    tie_break: TieBreak,
+    max_bids: u32,
+    allow_bid_revision: bool,
+    allowlist_root: Option<[u8; 32]>,
+    max_bid_spend: u64,
     nonce: u128,
 ) -> Result<()> {
+    require!(
+        end_time > Clock::get()?.unix_timestamp,
+        ErrorCode::InvalidEndTime
+    );
+    require!(sale_amount > 0, ErrorCode::InvalidSaleAmount);
+    // Single-lot formats sell the whole supply as one unit.
+    let units_valid = match auction_type {
+        AuctionType::FirstPrice | AuctionType::SecondPrice => total_units == 1,
+        AuctionType::UniformPrice | AuctionType::Discriminatory => total_units > 0,
+    };
+    require!(units_valid, ErrorCode::InvalidTotalUnits);
+    require!(max_bids > 0, ErrorCode::InvalidMaxBids);
+    // A single lot cannot be split between tied bidders.
+    require!(
+        tie_break != TieBreak::ProRata
+            || matches!(
+                auction_type,
+                AuctionType::UniformPrice | AuctionType::Discriminatory
+            ),
+        ErrorCode::InvalidTieBreak
+    );
+
+    // Deposit the sale supply up front. With a Token-2022 transfer fee the vault
+    // receives less than `sale_amount`, so the auction records what actually arrived.
+    token_interface::transfer_checked(
+        CpiContext::new(
+            ctx.accounts.sale_token_program.to_account_info(),
+            TransferChecked {
+                from: ctx.accounts.authority_sale_account.to_account_info(),
+                mint: ctx.accounts.sale_mint.to_account_info(),
+                to: ctx.accounts.sale_vault.to_account_info(),
+                authority: ctx.accounts.authority.to_account_info(),
+            },
+        ),
+        sale_amount,
+        ctx.accounts.sale_mint.decimals,
+    )?;
+    ctx.accounts.sale_vault.reload()?;
+    let sale_received = ctx.accounts.sale_vault.amount;
+    require!(sale_received >= total_units, ErrorCode::InvalidTotalUnits);
+
     let auction = &mut ctx.accounts.auction;
     auction.bump = ctx.bumps.auction;
     auction.authority = ctx.accounts.authority.key();
+    auction.auction_id = auction_id;
     auction.auction_type = auction_type;
     auction.status = AuctionStatus::Open;
     auction.min_bid = min_bid;
     auction.end_time = end_time;
     auction.bid_count = 0;
-    auction.state_nonce = nonce;
-    auction.encrypted_state = [[0u8; 32]; 5];
+    auction.sale_mint = ctx.accounts.sale_mint.key();
+    auction.quote_mint = ctx.accounts.quote_mint.key();
+    auction.sale_vault = ctx.accounts.sale_vault.key();
+    auction.quote_vault = ctx.accounts.quote_vault.key();
+    auction.sale_amount = sale_received;
+    auction.total_units = total_units;
+    auction.proceeds_claimed = false;
+    auction.tie_break = tie_break;
+    auction.max_bids = max_bids;
+    auction.allow_bid_revision = allow_bid_revision;
+    auction.allowlist_root = allowlist_root;
+    auction.max_bid_spend = max_bid_spend;
+    auction.rerank_required = false;
+    auction.start_computation(
+        ComputationKind::InitAuctionState,
+        ctx.accounts.computation_account.key(),
+    )?;
+    auction.bids_in_flight = 0;
+
+    let auction_state = &mut ctx.accounts.auction_state;
+    auction_state.bump = ctx.bumps.auction_state;
+    auction_state.auction = ctx.accounts.auction.key();
+    auction_state.state_nonce = nonce;
+    auction_state.state_version = 0;
+    auction_state.encrypted_state = vec![[0u8; 32]; ENCRYPTED_STATE_FIELDS];
+
+    let bid_queue = &mut ctx.accounts.bid_queue;
+    bid_queue.bump = ctx.bumps.bid_queue;
+    bid_queue.auction = ctx.accounts.auction.key();
+    bid_queue.next_seq = 0;
+    bid_queue.processed_seq = 0;
+    bid_queue.in_flight = None;
+    bid_queue.rerank_from = 0;
+    bid_queue.reranked = 0;
+    bid_queue.entries = Vec::new();
+
+    let vault = &mut ctx.accounts.vault;
+    vault.bump = ctx.bumps.vault;
+    vault.auction = ctx.accounts.auction.key();
+
+    let allocations = &mut ctx.accounts.allocations;
+    allocations.bump = ctx.bumps.allocations;
+    allocations.auction = ctx.accounts.auction.key();
+    allocations.clearing_price = 0;
+    allocations.winner = Pubkey::default();
+    allocations.resolved_at = 0;
+    allocations.allocations = [Allocation::default(); MAX_WINNERS];
 
     ctx.accounts.sign_pda_account.bump = ctx.bumps.sign_pda_account;
 
@@ -83,10 +317,16 @@ pub fn init_auction_state(
         vec![InitAuctionStateCallback::callback_ix(
             computation_offset,
             &ctx.accounts.mxe_account,
-            &[CallbackAccount {
-                pubkey: ctx.accounts.auction.key(),
-                is_writable: true,
-            }],
+            &[
+                CallbackAccount {
+                    pubkey: ctx.accounts.auction.key(),
+                    is_writable: true,
+                },
+                CallbackAccount {
+                    pubkey: ctx.accounts.auction_state.key(),
+                    is_writable: true,
+                },
+            ],
         )?],
         1,
         0,
@@ -100,66 +340,466 @@ pub fn init_auction_state_callback(
     ctx: Context<InitAuctionStateCallback>,
     output: SignedComputationOutputs<InitAuctionStateOutput>,
 ) -> Result<()> {
+    if !ctx
+        .accounts
+        .auction
+        .is_pending(&ctx.accounts.computation_account.key())
+    {
+        emit!(StaleComputationEvent {
+            auction: ctx.accounts.auction.key(),
+            bidder: None,
+            kind: ComputationKind::InitAuctionState,
+            computation_account: ctx.accounts.computation_account.key(),
+        });
+        return Ok(());
+    }
+
     let o = match output.verify_output(
         &ctx.accounts.cluster_account,
         &ctx.accounts.computation_account,
     ) {
         Ok(InitAuctionStateOutput { field_0 }) => field_0,
-        Err(_) => return Err(ErrorCode::AbortedComputation.into()),
+        Err(_) => {
+            let auction = &mut ctx.accounts.auction;
+            auction.fail_pending_computation();
+            emit!(ComputationFailedEvent {
+                auction: auction.key(),
+                kind: ComputationKind::InitAuctionState,
+                computation_account: ctx.accounts.computation_account.key(),
+            });
+            return Ok(());
+        }
     };
 
     let auction_key = ctx.accounts.auction.key();
     let authority = ctx.accounts.auction.authority;
+    let auction_id = ctx.accounts.auction.auction_id;
     let auction_type = ctx.accounts.auction.auction_type;
     let min_bid = ctx.accounts.auction.min_bid;
     let end_time = ctx.accounts.auction.end_time;
+    let sale_mint = ctx.accounts.auction.sale_mint;
+    let quote_mint = ctx.accounts.auction.quote_mint;
+    let sale_amount = ctx.accounts.auction.sale_amount;
+    let total_units = ctx.accounts.auction.total_units;
+    let tie_break = ctx.accounts.auction.tie_break;
+    let max_bids = ctx.accounts.auction.max_bids;
+    let allow_bid_revision = ctx.accounts.auction.allow_bid_revision;
+    let allowlist_root = ctx.accounts.auction.allowlist_root;
+    let max_bid_spend = ctx.accounts.auction.max_bid_spend;
 
-    let auction = &mut ctx.accounts.auction;
-    auction.encrypted_state = o.ciphertexts;
-    auction.state_nonce = o.nonce;
+    ctx.accounts.auction_state.write(&o.ciphertexts, o.nonce);
+    ctx.accounts.auction.pending_computation = None;
 
     emit!(AuctionCreatedEvent {
         auction: auction_key,
         authority,
+        auction_id,
         auction_type,
         min_bid,
         end_time,
+        sale_mint,
+        quote_mint,
+        sale_amount,
+        total_units,
+        tie_break,
+        max_bids,
+        allow_bid_revision,
+        allowlist_root,
+        max_bid_spend,
     });
 
     Ok(())
 }
+/// Re-queues `init_auction_state` after it aborted, or after its callback has
+/// not landed within `COMPUTATION_TIMEOUT`. Bidding stays closed until it succeeds.
+pub fn retry_init_auction_state(
+    ctx: Context<RetryInitAuctionState>,
+    computation_offset: u64,
+    nonce: u128,
+) -> Result<()> {
+    let auction = &mut ctx.accounts.auction;
+    require!(
+        auction.can_retry(
+            ComputationKind::InitAuctionState,
+            Clock::get()?.unix_timestamp
+        ),
+        ErrorCode::NothingToRetry
+    );
+    auction.start_computation(
+        ComputationKind::InitAuctionState,
+        ctx.accounts.computation_account.key(),
+    )?;
+
+    ctx.accounts.sign_pda_account.bump = ctx.bumps.sign_pda_account;
+
+    let args = ArgBuilder::new().plaintext_u128(nonce).build();
+
+    queue_computation(
+        ctx.accounts,
+        computation_offset,
+        args,
+        None,
+        vec![InitAuctionStateCallback::callback_ix(
+            computation_offset,
+            &ctx.accounts.mxe_account,
+            &[
+                CallbackAccount {
+                    pubkey: ctx.accounts.auction.key(),
+                    is_writable: true,
+                },
+                CallbackAccount {
+                    pubkey: ctx.accounts.auction_state.key(),
+                    is_writable: true,
+                },
+            ],
+        )?],
+        1,
+        0,
+    )?;
+
+    Ok(())
+}
 pub fn place_bid(
     ctx: Context<PlaceBid>,
-    computation_offset: u64,
-    encrypted_bidder_lo: [u8; 32],
-    encrypted_bidder_hi: [u8; 32],
     encrypted_amount: [u8; 32],
+    encrypted_quantity: [u8; 32],
+    encrypted_max_spend: [u8; 32],
     bidder_pubkey: [u8; 32],
     nonce: u128,
+    deposit: u64,
+    max_allocation: u64,
+    allowlist_proof: Vec<[u8; 32]>,
 ) -> Result<()> {
     let auction = &ctx.accounts.auction;
     require!(
         auction.status == AuctionStatus::Open,
         ErrorCode::AuctionNotOpen
     );
+    // Without an allowlist every bidder may take the whole supply.
+    let max_units = match auction.allowlist_root {
+        Some(root) => {
+            require!(
+                allowlist_contains(
+                    &root,
+                    &ctx.accounts.bidder.key(),
+                    max_allocation,
+                    &allowlist_proof
+                ),
+                ErrorCode::NotAllowlisted
+            );
+            if max_allocation == 0 {
+                auction.total_units
+            } else {
+                max_allocation.min(auction.total_units)
+            }
+        }
+        None => auction.total_units,
+    };
+    require!(
+        Clock::get()?.unix_timestamp < auction.end_time,
+        ErrorCode::BiddingEnded
+    );
+    // The encrypted state only exists once `init_auction_state` has succeeded.
+    require!(
+        auction.pending_computation.is_none(),
+        ErrorCode::ComputationPending
+    );
+    // One bid per signer: a second bid is either rejected or, when the auction
+    // allows it, replaces the first inside the circuit.
+    require!(
+        auction.allow_bid_revision || !ctx.accounts.receipt.has_bid(),
+        ErrorCode::DuplicateBid
+    );
+    require!(!ctx.accounts.receipt.pending, ErrorCode::ComputationPending);
+    // At most one queued entry per bidder, so revisions cannot crowd others out
+    // of the shared queue.
+    require!(
+        !ctx.accounts.receipt.active
+            || ctx.accounts.bid_queue.is_processed(ctx.accounts.receipt.queue_seq),
+        ErrorCode::BidAlreadyQueued
+    );
+    require!(
+        ctx.accounts.bid_queue.entries.len() < BID_QUEUE_CAPACITY,
+        ErrorCode::BidQueueFull
+    );
+    // Revising a bid does not take another place under the cap.
+    require!(
+        ctx.accounts.receipt.active || auction.bid_count < auction.max_bids,
+        ErrorCode::MaxBidsReached
+    );
+
+    // The deposit is the upper bound of the bid: the circuit ignores any bid
+    // above it, so the quote vault always holds enough to pay the winning price.
+    // Only what actually reached the vault counts, which keeps Token-2022
+    // transfer fees out of the escrow.
+    let vault_balance_before = ctx.accounts.quote_vault.amount;
+    match &ctx.accounts.bidder_quote_account {
+        Some(bidder_quote_account) => token_interface::transfer_checked(
+            CpiContext::new(
+                ctx.accounts.quote_token_program.to_account_info(),
+                TransferChecked {
+                    from: bidder_quote_account.to_account_info(),
+                    mint: ctx.accounts.quote_mint.to_account_info(),
+                    to: ctx.accounts.quote_vault.to_account_info(),
+                    authority: ctx.accounts.bidder.to_account_info(),
+                },
+            ),
+            deposit,
+            ctx.accounts.quote_mint.decimals,
+        )?,
+        // Without a token account the deposit is paid in lamports and wrapped
+        // straight into the vault, which only works for a wrapped SOL quote mint.
+        None => {
+            require!(
+                is_native_mint(&ctx.accounts.quote_mint.key()),
+                ErrorCode::NativeDepositNotSupported
+            );
+            anchor_lang::system_program::transfer(
+                CpiContext::new(
+                    ctx.accounts.system_program.to_account_info(),
+                    anchor_lang::system_program::Transfer {
+                        from: ctx.accounts.bidder.to_account_info(),
+                        to: ctx.accounts.quote_vault.to_account_info(),
+                    },
+                ),
+                deposit,
+            )?;
+            token_interface::sync_native(CpiContext::new(
+                ctx.accounts.quote_token_program.to_account_info(),
+                token_interface::SyncNative {
+                    account: ctx.accounts.quote_vault.to_account_info(),
+                },
+            ))?;
+        }
+    }
+    ctx.accounts.quote_vault.reload()?;
+    let received = ctx
+        .accounts
+        .quote_vault
+        .amount
+        .checked_sub(vault_balance_before)
+        .ok_or(ErrorCode::MathOverflow)?;
+    let receipt = &mut ctx.accounts.receipt;
+    receipt.bump = ctx.bumps.receipt;
+    receipt.auction = auction.key();
+    receipt.bidder = ctx.accounts.bidder.key();
+    receipt.deposit = receipt
+        .deposit
+        .checked_add(received)
+        .ok_or(ErrorCode::MathOverflow)?;
+    // A revision replaces the earlier bid, so everything the bidder has
+    // deposited so far backs the new one.
+    require!(receipt.deposit >= auction.min_bid, ErrorCode::DepositTooLow);
+    receipt.placed_at = Clock::get()?.unix_timestamp;
+    receipt.computation_offset = 0;
+    receipt.confirmed = false;
+    receipt.encryption_pubkey = bidder_pubkey;
+    receipt.nonce = nonce;
+    receipt.ciphertexts = [encrypted_amount, encrypted_quantity, encrypted_max_spend];
+    receipt.max_units = max_units;
+    // A queued bid is folded in by `process_bids` even if the auction has
+    // closed by then, so it counts from the moment it is queued.
+    let newly_active = !receipt.active;
+    receipt.active = true;
+
+    let bid_queue = &mut ctx.accounts.bid_queue;
+    receipt.queue_seq = bid_queue.next_seq;
+    bid_queue.next_seq += 1;
+    bid_queue.entries.push(receipt.queued_bid());
+
+    let auction = &mut ctx.accounts.auction;
+    if newly_active {
+        auction.bid_count = auction
+            .bid_count
+            .checked_add(1)
+            .ok_or(ErrorCode::MathOverflow)?;
+    } else {
+        auction.note_ranked_bid_removed();
+    }
+    auction.bids_in_flight = auction
+        .bids_in_flight
+        .checked_add(1)
+        .ok_or(ErrorCode::MathOverflow)?;
+
+    emit!(BidPlacedEvent {
+        auction: auction.key(),
+        bid_count: auction.bid_count,
+    });
+
+    Ok(())
+}
+/// Folds the oldest queued bids, up to `BID_BATCH_SIZE`, into the encrypted
+/// auction state. Anyone can call it, and it keeps working after the auction
+/// closes so the queue can drain before resolution. The receipts of the batched
+/// bids are passed as remaining accounts, in queue order, so the callback can
+/// mark them confirmed. Bids leave the queue only when their batch lands, so an
+/// aborted, stale or expired batch is simply processed again.
+///
+/// Nothing calls this on its own, and throughput is one batch per MPC round
+/// trip. Operators expecting a burst of bids near `end_time` should keep a crank
+/// running and leave time after closing to drain the queue before resolving.
+pub fn process_bids(ctx: Context<ProcessBids>, computation_offset: u64) -> Result<()> {
+    let auction = &ctx.accounts.auction;
+    require!(!auction.is_finalized(), ErrorCode::AuctionAlreadyFinalized);
+    require!(
+        !matches!(
+            auction.pending_computation,
+            Some(PendingComputation { kind: ComputationKind::InitAuctionState, .. })
+        ),
+        ErrorCode::ComputationPending
+    );
+    let bid_queue = &mut ctx.accounts.bid_queue;
+    require!(bid_queue.in_flight.is_none(), ErrorCode::ComputationPending);
+    require!(!bid_queue.entries.is_empty(), ErrorCode::BidQueueEmpty);
+
+    let count = bid_queue.entries.len().min(BID_BATCH_SIZE);
+    require!(
+        ctx.remaining_accounts.len() == count,
+        ErrorCode::InvalidBidReceipts
+    );
+    let auction_key = auction.key();
+    for (entry, receipt) in bid_queue.entries.iter().zip(ctx.remaining_accounts) {
+        let (expected, _) = Pubkey::find_program_address(
+            &[b"receipt", auction_key.as_ref(), entry.bidder.as_ref()],
+            &crate::ID,
+        );
+        require_keys_eq!(receipt.key(), expected, ErrorCode::InvalidBidReceipts);
+    }
+    bid_queue.in_flight = Some(PendingBatch {
+        computation_offset,
+        computation_account: ctx.accounts.computation_account.key(),
+        queued_at: Clock::get()?.unix_timestamp,
+        count: count as u8,
+        state_version: ctx.accounts.auction_state.state_version,
+        rerank_from: None,
+    });
+
+    ctx.accounts.sign_pda_account.bump = ctx.bumps.sign_pda_account;
+
+    let args = bid_batch_args(&ctx.accounts.bid_queue.entries[..count])
+        .plaintext_u8(count as u8)
+        .plaintext_u64(auction.min_bid)
+        .plaintext_u64(auction.max_bid_spend)
+        .plaintext_bool(false)
+        .plaintext_u128(ctx.accounts.auction_state.state_nonce)
+        .account(
+            ctx.accounts.auction_state.key(),
+            AuctionStateAccount::ENCRYPTED_STATE_OFFSET,
+            AuctionStateAccount::ENCRYPTED_STATE_SIZE,
+        )
+        .build();
+
+    let mut callback_accounts = vec![
+        CallbackAccount {
+            pubkey: ctx.accounts.auction.key(),
+            is_writable: true,
+        },
+        CallbackAccount {
+            pubkey: ctx.accounts.auction_state.key(),
+            is_writable: true,
+        },
+        CallbackAccount {
+            pubkey: ctx.accounts.bid_queue.key(),
+            is_writable: true,
+        },
+    ];
+    callback_accounts.extend(ctx.remaining_accounts.iter().map(|receipt| CallbackAccount {
+        pubkey: receipt.key(),
+        is_writable: true,
+    }));
+
+    queue_computation(
+        ctx.accounts,
+        computation_offset,
+        args,
+        None,
+        vec![ProcessBidsCallback::callback_ix(
+            computation_offset,
+            &ctx.accounts.mxe_account,
+            &callback_accounts,
+        )?],
+        1,
+        0,
+    )?;
+
+    Ok(())
+}
+/// Rebuilds the ranking from the receipts of every active bid once bidding has
+/// closed and the queue has drained. Needed only when `Auction::rerank_required`
+/// is set. Each call folds up to `BID_BATCH_SIZE` receipts, passed as remaining
+/// accounts in ascending `queue_seq` order, which is the order the bids arrived
+/// in; the first batch starts from an empty ranking. Anyone can call it.
+pub fn rerank_bids(ctx: Context<RerankBids>, computation_offset: u64) -> Result<()> {
+    let auction = &ctx.accounts.auction;
+    require!(
+        auction.status == AuctionStatus::Closed,
+        ErrorCode::AuctionNotClosed
+    );
+    require!(auction.rerank_required, ErrorCode::NothingToRerank);
+    require!(auction.bids_in_flight == 0, ErrorCode::BidsInFlight);
+    require!(
+        auction.pending_computation.is_none(),
+        ErrorCode::ComputationPending
+    );
+    require!(
+        ctx.accounts.bid_queue.in_flight.is_none(),
+        ErrorCode::ComputationPending
+    );
+    // Every bid was cancelled, and each cancellation emptied its slot.
+    if auction.bid_count == 0 {
+        ctx.accounts.auction.rerank_required = false;
+        return Ok(());
+    }
+
+    let count = ctx.remaining_accounts.len();
+    require!(
+        count > 0
+            && count <= BID_BATCH_SIZE
+            && ctx.accounts.bid_queue.reranked as usize + count <= auction.bid_count as usize,
+        ErrorCode::InvalidBidReceipts
+    );
+    // Strictly ascending sequence numbers fold every bid at most once, so the
+    // pass is complete once it has folded `bid_count` of them.
+    let auction_key = auction.key();
+    let mut rerank_from = ctx.accounts.bid_queue.rerank_from;
+    let mut entries = Vec::with_capacity(count);
+    for info in ctx.remaining_accounts.iter() {
+        require_keys_eq!(*info.owner, crate::ID, ErrorCode::InvalidBidReceipts);
+        let receipt = BidReceipt::try_deserialize(&mut &info.try_borrow_data()?[..])?;
+        let (expected, _) = Pubkey::find_program_address(
+            &[b"receipt", auction_key.as_ref(), receipt.bidder.as_ref()],
+            &crate::ID,
+        );
+        require_keys_eq!(info.key(), expected, ErrorCode::InvalidBidReceipts);
+        require!(
+            receipt.active && receipt.queue_seq >= rerank_from,
+            ErrorCode::InvalidBidReceipts
+        );
+        rerank_from = receipt.queue_seq + 1;
+        entries.push(receipt.queued_bid());
+    }
+    let reset = ctx.accounts.bid_queue.reranked == 0;
+    ctx.accounts.bid_queue.in_flight = Some(PendingBatch {
+        computation_offset,
+        computation_account: ctx.accounts.computation_account.key(),
+        queued_at: Clock::get()?.unix_timestamp,
+        count: count as u8,
+        state_version: ctx.accounts.auction_state.state_version,
+        rerank_from: Some(rerank_from),
+    });
 
     ctx.accounts.sign_pda_account.bump = ctx.bumps.sign_pda_account;
 
-    // Account offset: 8 (discriminator) + 1 + 32 + 1 + 1 + 8 + 8 + 1 + 16 = 76
-    const ENCRYPTED_STATE_OFFSET: u32 = 76;
-    const ENCRYPTED_STATE_SIZE: u32 = 32 * 5;
-
-    let args = ArgBuilder::new()
-        .x25519_pubkey(bidder_pubkey)
-        .plaintext_u128(nonce)
-        .encrypted_u128(encrypted_bidder_lo)
-        .encrypted_u128(encrypted_bidder_hi)
-        .encrypted_u64(encrypted_amount)
-        .plaintext_u128(auction.state_nonce)
+    let args = bid_batch_args(&entries)
+        .plaintext_u8(count as u8)
+        .plaintext_u64(auction.min_bid)
+        .plaintext_u64(auction.max_bid_spend)
+        .plaintext_bool(reset)
+        .plaintext_u128(ctx.accounts.auction_state.state_nonce)
         .account(
-            ctx.accounts.auction.key(),
-            ENCRYPTED_STATE_OFFSET,
-            ENCRYPTED_STATE_SIZE,
+            ctx.accounts.auction_state.key(),
+            AuctionStateAccount::ENCRYPTED_STATE_OFFSET,
+            AuctionStateAccount::ENCRYPTED_STATE_SIZE,
         )
         .build();
 
@@ -168,13 +808,23 @@ pub fn place_bid(
         computation_offset,
         args,
         None,
-        vec![PlaceBidCallback::callback_ix(
+        vec![ProcessBidsCallback::callback_ix(
             computation_offset,
             &ctx.accounts.mxe_account,
-            &[CallbackAccount {
-                pubkey: ctx.accounts.auction.key(),
-                is_writable: true,
-            }],
+            &[
+                CallbackAccount {
+                    pubkey: ctx.accounts.auction.key(),
+                    is_writable: true,
+                },
+                CallbackAccount {
+                    pubkey: ctx.accounts.auction_state.key(),
+                    is_writable: true,
+                },
+                CallbackAccount {
+                    pubkey: ctx.accounts.bid_queue.key(),
+                    is_writable: true,
+                },
+            ],
         )?],
         1,
         0,
@@ -182,57 +832,161 @@ pub fn place_bid(
 
     Ok(())
 }
-#[arcium_callback(encrypted_ix = "place_bid")]
-    pub fn place_bid_callback(
-        ctx: Context<PlaceBidCallback>,
-        output: SignedComputationOutputs<PlaceBidOutput>,
+#[arcium_callback(encrypted_ix = "process_bids")]
+    pub fn process_bids_callback(
+        ctx: Context<ProcessBidsCallback>,
+        output: SignedComputationOutputs<ProcessBidsOutput>,
     ) -> Result<()> {
+        // A batch cleared by `expire_bid_batch` can still call back late, after
+        // its bids were queued again. Only the batch in flight may touch the queue.
+        let batch = match ctx.accounts.bid_queue.in_flight {
+            Some(batch) if batch.computation_account == ctx.accounts.computation_account.key() => {
+                batch
+            }
+            _ => {
+                emit!(StaleComputationEvent {
+                    auction: ctx.accounts.auction.key(),
+                    bidder: None,
+                    kind: ComputationKind::ProcessBids,
+                    computation_account: ctx.accounts.computation_account.key(),
+                });
+                return Ok(());
+            }
+        };
+        ctx.accounts.bid_queue.in_flight = None;
+
         let o = match output.verify_output(
             &ctx.accounts.cluster_account,
             &ctx.accounts.computation_account,
         ) {
-            Ok(PlaceBidOutput { field_0 }) => field_0,
-            Err(_) => return Err(ErrorCode::AbortedComputation.into()),
+            Ok(ProcessBidsOutput { field_0 }) => field_0,
+            Err(_) => {
+                // The batch stays at the head of the queue for the next `process_bids`.
+                emit!(ComputationFailedEvent {
+                    auction: ctx.accounts.auction.key(),
+                    kind: ComputationKind::ProcessBids,
+                    computation_account: ctx.accounts.computation_account.key(),
+                });
+                return Ok(());
+            }
         };
 
-        let auction_key = ctx.accounts.auction.key();
+        // A cancellation landed after this batch was queued, so the batch started
+        // from an outdated state. Writing it would undo the cancellation.
+        if ctx.accounts.auction_state.state_version != batch.state_version {
+            emit!(StaleComputationEvent {
+                auction: ctx.accounts.auction.key(),
+                bidder: None,
+                kind: ComputationKind::ProcessBids,
+                computation_account: ctx.accounts.computation_account.key(),
+            });
+            return Ok(());
+        }
+
+        ctx.accounts.auction_state.write(&o.ciphertexts, o.nonce);
         let auction = &mut ctx.accounts.auction;
-        auction.encrypted_state = o.ciphertexts;
-        auction.state_nonce = o.nonce;
-        auction.bid_count += 1;
+        if let Some(rerank_from) = batch.rerank_from {
+            let bid_queue = &mut ctx.accounts.bid_queue;
+            bid_queue.rerank_from = rerank_from;
+            bid_queue.reranked += batch.count as u32;
+            if bid_queue.reranked >= auction.bid_count {
+                auction.rerank_required = false;
+            }
+            emit!(BidsRerankedEvent {
+                auction: auction.key(),
+                reranked: bid_queue.reranked,
+                bid_count: auction.bid_count,
+            });
+            return Ok(());
+        }
+        auction.bids_in_flight = auction.bids_in_flight.saturating_sub(batch.count as u32);
+        let bid_queue = &mut ctx.accounts.bid_queue;
+        bid_queue.entries.drain(..batch.count as usize);
+        bid_queue.processed_seq += batch.count as u64;
 
-        emit!(BidPlacedEvent {
-            auction: auction_key,
-            bid_count: auction.bid_count,
+        // Receipts were checked when the batch was queued.
+        for info in ctx.remaining_accounts.iter() {
+            require_keys_eq!(*info.owner, crate::ID, ErrorCode::InvalidBidReceipts);
+            let mut data = info.try_borrow_mut_data()?;
+            let mut receipt = BidReceipt::try_deserialize(&mut &data[..])?;
+            if bid_queue.is_processed(receipt.queue_seq) {
+                receipt.confirmed = true;
+                receipt.computation_offset = batch.computation_offset;
+                let mut writer: &mut [u8] = &mut data[..];
+                receipt.try_serialize(&mut writer)?;
+            }
+        }
+
+        emit!(BidsProcessedEvent {
+            auction: ctx.accounts.auction.key(),
+            processed: batch.count,
+            queued: bid_queue.entries.len() as u32,
         });
 
         Ok(())
     }
-    pub fn first_winner(
-        ctx: Context<FirstWinner>,
-        computation_offset: u64,
-    ) -> Result<()> {
+    /// Clears a batch whose callback has not landed within `COMPUTATION_TIMEOUT`,
+    /// so `process_bids` or `rerank_bids` can queue its bids again. Anyone can call it.
+    pub fn expire_bid_batch(ctx: Context<ExpireBidBatch>) -> Result<()> {
+        let bid_queue = &mut ctx.accounts.bid_queue;
+        let batch = bid_queue.in_flight.ok_or(ErrorCode::NoBatchInFlight)?;
+        require!(
+            Clock::get()?.unix_timestamp >= batch.queued_at.saturating_add(COMPUTATION_TIMEOUT),
+            ErrorCode::ComputationNotExpired
+        );
+        bid_queue.in_flight = None;
+
+        emit!(ComputationFailedEvent {
+            auction: ctx.accounts.auction.key(),
+            kind: ComputationKind::ProcessBids,
+            computation_account: batch.computation_account,
+        });
+
+        Ok(())
+    }
+    /// Withdraws the signer's bid from the auction while bidding is still open.
+    /// The deposit stays in escrow and is refunded in full at settlement.
+    pub fn cancel_bid(ctx: Context<CancelBid>, computation_offset: u64) -> Result<()> {
         let auction = &ctx.accounts.auction;
         require!(
-            auction.status == AuctionStatus::Closed,
-            ErrorCode::AuctionNotClosed
+            auction.status == AuctionStatus::Open,
+            ErrorCode::AuctionNotOpen
         );
         require!(
-            auction.auction_type == AuctionType::FirstPrice,
-            ErrorCode::WrongAuctionType
+            Clock::get()?.unix_timestamp < auction.end_time,
+            ErrorCode::BiddingEnded
         );
+        require!(!ctx.accounts.receipt.pending, ErrorCode::ComputationPending);
+        require!(ctx.accounts.receipt.active, ErrorCode::NoActiveBid);
+        // A bid still waiting in the queue would be folded in after the cancellation.
+        require!(
+            ctx.accounts.bid_queue.is_processed(ctx.accounts.receipt.queue_seq),
+            ErrorCode::ComputationPending
+        );
+
+        let receipt = &mut ctx.accounts.receipt;
+        receipt.pending = true;
+        receipt.state_version = ctx.accounts.auction_state.state_version;
+        receipt.cancel_computation_account = ctx.accounts.computation_account.key();
+        receipt.cancel_queued_at = Clock::get()?.unix_timestamp;
+        let auction = &mut ctx.accounts.auction;
+        auction.bids_in_flight = auction
+            .bids_in_flight
+            .checked_add(1)
+            .ok_or(ErrorCode::MathOverflow)?;
 
         ctx.accounts.sign_pda_account.bump = ctx.bumps.sign_pda_account;
 
-        const ENCRYPTED_STATE_OFFSET: u32 = 8 + 1 + 32 + 1 + 1 + 8 + 8 + 1 + 16;
-        const ENCRYPTED_STATE_SIZE: u32 = 32 * 5;
+        let (signer_lo, signer_hi) = pubkey_halves(&ctx.accounts.bidder.key());
 
         let args = ArgBuilder::new()
-            .plaintext_u128(auction.state_nonce)
+            .plaintext_u128(signer_lo)
+            .plaintext_u128(signer_hi)
+            .plaintext_u128(ctx.accounts.auction_state.state_nonce)
             .account(
-                ctx.accounts.auction.key(),
-                ENCRYPTED_STATE_OFFSET,
-                ENCRYPTED_STATE_SIZE,
+                ctx.accounts.auction_state.key(),
+                AuctionStateAccount::ENCRYPTED_STATE_OFFSET,
+                AuctionStateAccount::ENCRYPTED_STATE_SIZE,
             )
             .build();
 
@@ -241,13 +995,23 @@ pub fn place_bid(
             computation_offset,
             args,
             None,
-            vec![FirstWinnerCallback::callback_ix(
+            vec![CancelBidCallback::callback_ix(
                 computation_offset,
                 &ctx.accounts.mxe_account,
-                &[CallbackAccount {
-                    pubkey: ctx.accounts.auction.key(),
-                    is_writable: true,
-                }],
+                &[
+                    CallbackAccount {
+                        pubkey: ctx.accounts.auction.key(),
+                        is_writable: true,
+                    },
+                    CallbackAccount {
+                        pubkey: ctx.accounts.auction_state.key(),
+                        is_writable: true,
+                    },
+                    CallbackAccount {
+                        pubkey: ctx.accounts.receipt.key(),
+                        is_writable: true,
+                    },
+                ],
             )?],
             1,
             0,
@@ -255,73 +1019,247 @@ pub fn place_bid(
 
         Ok(())
     }
-
-    #[arcium_callback(encrypted_ix = "first_winner")]
-    pub fn first_winner_callback(
-        ctx: Context<FirstWinnerCallback>,
-        output: SignedComputationOutputs<FirstWinnerOutput>,
+    #[arcium_callback(encrypted_ix = "cancel_bid")]
+    pub fn cancel_bid_callback(
+        ctx: Context<CancelBidCallback>,
+        output: SignedComputationOutputs<CancelBidOutput>,
     ) -> Result<()> {
-        let (winner_lo, winner_hi, payment_amount) = match output.verify_output(
+        // A cancellation cleared by `expire_cancel_bid` can still call back late.
+        if !ctx.accounts.receipt.pending
+            || ctx.accounts.receipt.cancel_computation_account
+                != ctx.accounts.computation_account.key()
+        {
+            emit!(StaleComputationEvent {
+                auction: ctx.accounts.auction.key(),
+                bidder: Some(ctx.accounts.receipt.bidder),
+                kind: ComputationKind::CancelBid,
+                computation_account: ctx.accounts.computation_account.key(),
+            });
+            return Ok(());
+        }
+
+        let o = match output.verify_output(
             &ctx.accounts.cluster_account,
             &ctx.accounts.computation_account,
         ) {
-            Ok(FirstWinnerOutput {
-                field_0:
-                    FirstWinnerOutputStruct0 {
-                        field_0: winner_lo,
-                        field_1: winner_hi,
-                        field_2: payment_amount,
-                    },
-            }) => (winner_lo, winner_hi, payment_amount),
-            Err(_) => return Err(ErrorCode::AbortedComputation.into()),
+            Ok(CancelBidOutput { field_0 }) => field_0,
+            Err(_) => {
+                // The bid is still in place; the bidder can call `cancel_bid` again.
+                ctx.accounts.receipt.pending = false;
+                ctx.accounts.auction.bids_in_flight =
+                    ctx.accounts.auction.bids_in_flight.saturating_sub(1);
+                emit!(ComputationFailedEvent {
+                    auction: ctx.accounts.auction.key(),
+                    kind: ComputationKind::CancelBid,
+                    computation_account: ctx.accounts.computation_account.key(),
+                });
+                return Ok(());
+            }
         };
 
-        let mut winner = [0u8; 32];
-        winner[..16].copy_from_slice(&winner_lo.to_le_bytes());
-        winner[16..].copy_from_slice(&winner_hi.to_le_bytes());
+        ctx.accounts.receipt.pending = false;
+        ctx.accounts.auction.bids_in_flight = ctx.accounts.auction.bids_in_flight.saturating_sub(1);
+        // Bidding closed while the cancellation was in flight, and the state may
+        // already be read for resolution, so the bid stands.
+        if ctx.accounts.auction.status != AuctionStatus::Open
+            || ctx.accounts.auction_state.state_version != ctx.accounts.receipt.state_version
+        {
+            emit!(StaleComputationEvent {
+                auction: ctx.accounts.auction.key(),
+                bidder: Some(ctx.accounts.receipt.bidder),
+                kind: ComputationKind::CancelBid,
+                computation_account: ctx.accounts.computation_account.key(),
+            });
+            return Ok(());
+        }
 
         let auction_key = ctx.accounts.auction.key();
-        let auction_type = ctx.accounts.auction.auction_type;
         let auction = &mut ctx.accounts.auction;
-        auction.status = AuctionStatus::Resolved;
+        ctx.accounts.auction_state.write(&o.ciphertexts, o.nonce);
+        let receipt = &mut ctx.accounts.receipt;
+        if receipt.active {
+            auction.note_ranked_bid_removed();
+            receipt.active = false;
+            auction.bid_count = auction.bid_count.saturating_sub(1);
+        }
 
-        emit!(AuctionResolvedEvent {
+        emit!(BidCancelledEvent {
             auction: auction_key,
-            winner,
-            payment_amount,
-            auction_type,
+            bidder: receipt.bidder,
+            bid_count: auction.bid_count,
         });
 
         Ok(())
     }
-    pub fn second_winner(
-        ctx: Context<SecondWinner>,
-        computation_offset: u64,
-    ) -> Result<()> {
-        let auction = &ctx.accounts.auction;
+    /// Gives up on a cancellation whose callback has not landed within
+    /// `COMPUTATION_TIMEOUT`, so it no longer holds up resolution. The bid stays
+    /// in place and the bidder can cancel again. Anyone can call it.
+    pub fn expire_cancel_bid(ctx: Context<ExpireCancelBid>) -> Result<()> {
+        let receipt = &mut ctx.accounts.receipt;
+        require!(receipt.pending, ErrorCode::NothingToRetry);
         require!(
-            auction.status == AuctionStatus::Closed,
-            ErrorCode::AuctionNotClosed
-        );
-        require!(
-            auction.auction_type == AuctionType::SecondPrice,
-            ErrorCode::WrongAuctionType
+            Clock::get()?.unix_timestamp
+                >= receipt.cancel_queued_at.saturating_add(COMPUTATION_TIMEOUT),
+            ErrorCode::ComputationNotExpired
         );
+        receipt.pending = false;
+        let auction = &mut ctx.accounts.auction;
+        auction.bids_in_flight = auction.bids_in_flight.saturating_sub(1);
 
-        ctx.accounts.sign_pda_account.bump = ctx.bumps.sign_pda_account;
-
-        const ENCRYPTED_STATE_OFFSET: u32 = 8 + 1 + 32 + 1 + 1 + 8 + 8 + 1 + 16;
-        const ENCRYPTED_STATE_SIZE: u32 = 32 * 5;
+        emit!(ComputationFailedEvent {
+            auction: auction.key(),
+            kind: ComputationKind::CancelBid,
+            computation_account: receipt.cancel_computation_account,
+        });
+
+        Ok(())
+    }
+    pub fn first_winner(
+        ctx: Context<FirstWinner>,
+        computation_offset: u64,
+    ) -> Result<()> {
+        let auction = &ctx.accounts.auction;
+        auction.ensure_resolvable(AuctionType::FirstPrice)?;
+
+        ctx.accounts.sign_pda_account.bump = ctx.bumps.sign_pda_account;
+
+        let args = ArgBuilder::new()
+            .plaintext_u8(auction.tie_break as u8)
+            .plaintext_u128(ctx.accounts.auction_state.state_nonce)
+            .account(
+                ctx.accounts.auction_state.key(),
+                AuctionStateAccount::ENCRYPTED_STATE_OFFSET,
+                AuctionStateAccount::ENCRYPTED_STATE_SIZE,
+            )
+            .build();
+
+        ctx.accounts.auction.start_computation(
+            ComputationKind::Resolve,
+            ctx.accounts.computation_account.key(),
+        )?;
+
+        queue_computation(
+            ctx.accounts,
+            computation_offset,
+            args,
+            None,
+            vec![FirstWinnerCallback::callback_ix(
+                computation_offset,
+                &ctx.accounts.mxe_account,
+                &[
+                    CallbackAccount {
+                        pubkey: ctx.accounts.auction.key(),
+                        is_writable: true,
+                    },
+                    CallbackAccount {
+                        pubkey: ctx.accounts.allocations.key(),
+                        is_writable: true,
+                    },
+                ],
+            )?],
+            1,
+            0,
+        )?;
+
+        Ok(())
+    }
+
+    #[arcium_callback(encrypted_ix = "first_winner")]
+    pub fn first_winner_callback(
+        ctx: Context<FirstWinnerCallback>,
+        output: SignedComputationOutputs<FirstWinnerOutput>,
+    ) -> Result<()> {
+        if !ctx
+            .accounts
+            .auction
+            .is_pending(&ctx.accounts.computation_account.key())
+        {
+            emit!(StaleComputationEvent {
+                auction: ctx.accounts.auction.key(),
+                bidder: None,
+                kind: ComputationKind::Resolve,
+                computation_account: ctx.accounts.computation_account.key(),
+            });
+            return Ok(());
+        }
+
+        let (winner_lo, winner_hi, payment_amount, price_shared) = match output.verify_output(
+            &ctx.accounts.cluster_account,
+            &ctx.accounts.computation_account,
+        ) {
+            Ok(FirstWinnerOutput {
+                field_0:
+                    FirstWinnerOutputStruct0 {
+                        field_0: winner_lo,
+                        field_1: winner_hi,
+                        field_2: payment_amount,
+                        field_3: price_shared,
+                    },
+            }) => (winner_lo, winner_hi, payment_amount, price_shared),
+            Err(_) => {
+                let auction = &mut ctx.accounts.auction;
+                auction.fail_pending_computation();
+                emit!(ComputationFailedEvent {
+                    auction: auction.key(),
+                    kind: ComputationKind::Resolve,
+                    computation_account: ctx.accounts.computation_account.key(),
+                });
+                return Ok(());
+            }
+        };
+
+        let mut winner = [0u8; 32];
+        winner[..16].copy_from_slice(&winner_lo.to_le_bytes());
+        winner[16..].copy_from_slice(&winner_hi.to_le_bytes());
+
+        let total_units = ctx.accounts.auction.total_units;
+        ctx.accounts.allocations.record_single_winner(
+            Pubkey::new_from_array(winner),
+            total_units,
+            payment_amount,
+        );
+
+        let auction_key = ctx.accounts.auction.key();
+        let auction_type = ctx.accounts.auction.auction_type;
+        ctx.accounts
+            .auction
+            .finish_resolution(&mut ctx.accounts.allocations)?;
+
+        emit!(AuctionResolvedEvent {
+            auction: auction_key,
+            winner,
+            payment_amount,
+            auction_type,
+            price_shared,
+        });
+
+        Ok(())
+    }
+    pub fn second_winner(
+        ctx: Context<SecondWinner>,
+        computation_offset: u64,
+    ) -> Result<()> {
+        let auction = &ctx.accounts.auction;
+        auction.ensure_resolvable(AuctionType::SecondPrice)?;
+
+        ctx.accounts.sign_pda_account.bump = ctx.bumps.sign_pda_account;
 
         let args = ArgBuilder::new()
-            .plaintext_u128(auction.state_nonce)
+            .plaintext_u8(auction.tie_break as u8)
+            .plaintext_u64(auction.min_bid)
+            .plaintext_u128(ctx.accounts.auction_state.state_nonce)
             .account(
-                ctx.accounts.auction.key(),
-                ENCRYPTED_STATE_OFFSET,
-                ENCRYPTED_STATE_SIZE,
+                ctx.accounts.auction_state.key(),
+                AuctionStateAccount::ENCRYPTED_STATE_OFFSET,
+                AuctionStateAccount::ENCRYPTED_STATE_SIZE,
             )
             .build();
 
+        ctx.accounts.auction.start_computation(
+            ComputationKind::Resolve,
+            ctx.accounts.computation_account.key(),
+        )?;
+
         queue_computation(
             ctx.accounts,
             computation_offset,
@@ -330,10 +1268,16 @@ pub fn place_bid(
             vec![SecondWinnerCallback::callback_ix(
                 computation_offset,
                 &ctx.accounts.mxe_account,
-                &[CallbackAccount {
-                    pubkey: ctx.accounts.auction.key(),
-                    is_writable: true,
-                }],
+                &[
+                    CallbackAccount {
+                        pubkey: ctx.accounts.auction.key(),
+                        is_writable: true,
+                    },
+                    CallbackAccount {
+                        pubkey: ctx.accounts.allocations.key(),
+                        is_writable: true,
+                    },
+                ],
             )?],
             1,
             0,
@@ -346,7 +1290,21 @@ pub fn place_bid(
         ctx: Context<SecondWinnerCallback>,
         output: SignedComputationOutputs<SecondWinnerOutput>,
     ) -> Result<()> {
-        let (winner_lo, winner_hi, payment_amount) = match output.verify_output(
+        if !ctx
+            .accounts
+            .auction
+            .is_pending(&ctx.accounts.computation_account.key())
+        {
+            emit!(StaleComputationEvent {
+                auction: ctx.accounts.auction.key(),
+                bidder: None,
+                kind: ComputationKind::Resolve,
+                computation_account: ctx.accounts.computation_account.key(),
+            });
+            return Ok(());
+        }
+
+        let (winner_lo, winner_hi, payment_amount, price_shared) = match output.verify_output(
             &ctx.accounts.cluster_account,
             &ctx.accounts.computation_account,
         ) {
@@ -356,62 +1314,1764 @@ pub fn place_bid(
                         field_0: winner_lo,
                         field_1: winner_hi,
                         field_2: payment_amount,
+                        field_3: price_shared,
                     },
-            }) => (winner_lo, winner_hi, payment_amount),
-            Err(_) => return Err(ErrorCode::AbortedComputation.into()),
+            }) => (winner_lo, winner_hi, payment_amount, price_shared),
+            Err(_) => {
+                let auction = &mut ctx.accounts.auction;
+                auction.fail_pending_computation();
+                emit!(ComputationFailedEvent {
+                    auction: auction.key(),
+                    kind: ComputationKind::Resolve,
+                    computation_account: ctx.accounts.computation_account.key(),
+                });
+                return Ok(());
+            }
         };
 
         let mut winner = [0u8; 32];
         winner[..16].copy_from_slice(&winner_lo.to_le_bytes());
         winner[16..].copy_from_slice(&winner_hi.to_le_bytes());
 
+        let total_units = ctx.accounts.auction.total_units;
+        ctx.accounts.allocations.record_single_winner(
+            Pubkey::new_from_array(winner),
+            total_units,
+            payment_amount,
+        );
+
         let auction_key = ctx.accounts.auction.key();
         let auction_type = ctx.accounts.auction.auction_type;
-        let auction = &mut ctx.accounts.auction;
-        auction.status = AuctionStatus::Resolved;
+        ctx.accounts
+            .auction
+            .finish_resolution(&mut ctx.accounts.allocations)?;
 
         emit!(AuctionResolvedEvent {
             auction: auction_key,
             winner,
             payment_amount,
             auction_type,
+            price_shared,
         });
 
         Ok(())
     }
-}
-#[derive(Accounts)]
-pub struct CloseAuction<'info> {
-    #[account(mut)]
-    pub authority: Signer<'info>,
-    #[account(
-        mut,
-        has_one = authority @ ErrorCode::Unauthorized,
-    )]
-    pub auction: Account<'info, Auction>,
-}
+    pub fn uniform_price_winners(
+        ctx: Context<UniformPriceWinners>,
+        computation_offset: u64,
+    ) -> Result<()> {
+        let auction = &ctx.accounts.auction;
+        auction.ensure_resolvable(AuctionType::UniformPrice)?;
 
-#[account]
-#[derive(InitSpace)]
-pub struct Auction{
-    pub bump: u8,
-    pub authority: Pubkey,
-    pub auction_type: AuctionType,
-    pub min_bid: u64,
-    pub end_time: i64,
-    pub status: AuctionStatus,
-    pub bid_count: u8,
-    pub state_nonce: u128,
-    pub encrypted_state: [[u8; 32]; 5],
-}
-#[queue_computation_accounts("init_auction_state", authority)]
+        ctx.accounts.sign_pda_account.bump = ctx.bumps.sign_pda_account;
+
+        let args = ArgBuilder::new()
+            .plaintext_u8(auction.tie_break as u8)
+            .plaintext_u64(auction.total_units)
+            .plaintext_u128(ctx.accounts.auction_state.state_nonce)
+            .account(
+                ctx.accounts.auction_state.key(),
+                AuctionStateAccount::ENCRYPTED_STATE_OFFSET,
+                AuctionStateAccount::ENCRYPTED_STATE_SIZE,
+            )
+            .build();
+
+        ctx.accounts.auction.start_computation(
+            ComputationKind::Resolve,
+            ctx.accounts.computation_account.key(),
+        )?;
+
+        queue_computation(
+            ctx.accounts,
+            computation_offset,
+            args,
+            None,
+            vec![UniformPriceWinnersCallback::callback_ix(
+                computation_offset,
+                &ctx.accounts.mxe_account,
+                &[
+                    CallbackAccount {
+                        pubkey: ctx.accounts.auction.key(),
+                        is_writable: true,
+                    },
+                    CallbackAccount {
+                        pubkey: ctx.accounts.allocations.key(),
+                        is_writable: true,
+                    },
+                ],
+            )?],
+            1,
+            0,
+        )?;
+
+        Ok(())
+    }
+    #[arcium_callback(encrypted_ix = "uniform_price_winners")]
+    pub fn uniform_price_winners_callback(
+        ctx: Context<UniformPriceWinnersCallback>,
+        output: SignedComputationOutputs<UniformPriceWinnersOutput>,
+    ) -> Result<()> {
+        if !ctx
+            .accounts
+            .auction
+            .is_pending(&ctx.accounts.computation_account.key())
+        {
+            emit!(StaleComputationEvent {
+                auction: ctx.accounts.auction.key(),
+                bidder: None,
+                kind: ComputationKind::Resolve,
+                computation_account: ctx.accounts.computation_account.key(),
+            });
+            return Ok(());
+        }
+
+        let (clearing_price, winners_lo, winners_hi, quantities, price_shared) = match output.verify_output(
+            &ctx.accounts.cluster_account,
+            &ctx.accounts.computation_account,
+        ) {
+            Ok(UniformPriceWinnersOutput {
+                field_0:
+                    UniformPriceWinnersOutputStruct0 {
+                        field_0: clearing_price,
+                        field_1: winners_lo,
+                        field_2: winners_hi,
+                        field_3: quantities,
+                        field_4: price_shared,
+                    },
+            }) => (clearing_price, winners_lo, winners_hi, quantities, price_shared),
+            Err(_) => {
+                let auction = &mut ctx.accounts.auction;
+                auction.fail_pending_computation();
+                emit!(ComputationFailedEvent {
+                    auction: auction.key(),
+                    kind: ComputationKind::Resolve,
+                    computation_account: ctx.accounts.computation_account.key(),
+                });
+                return Ok(());
+            }
+        };
+
+        // Everyone pays the clearing price, so each allocation's payment is
+        // fixed here and settlement only has to read it back.
+        let allocations = &mut ctx.accounts.allocations;
+        allocations.clearing_price = clearing_price;
+        let mut units_sold: u64 = 0;
+        for (i, &quantity) in quantities.iter().enumerate() {
+            if quantity == 0 {
+                continue;
+            }
+            let payment = clearing_price
+                .checked_mul(quantity)
+                .ok_or(ErrorCode::MathOverflow)?;
+            allocations.allocations[i] = Allocation {
+                bidder: pubkey_from_halves(winners_lo[i], winners_hi[i]),
+                quantity,
+                payment,
+                claimed: false,
+            };
+            units_sold += quantity;
+        }
+
+        let auction_key = ctx.accounts.auction.key();
+        ctx.accounts
+            .auction
+            .finish_resolution(&mut ctx.accounts.allocations)?;
+
+        emit!(UniformPriceResolvedEvent {
+            auction: auction_key,
+            clearing_price,
+            units_sold,
+            price_shared,
+        });
+
+        Ok(())
+    }
+    pub fn discriminatory_winners(
+        ctx: Context<DiscriminatoryWinners>,
+        computation_offset: u64,
+    ) -> Result<()> {
+        let auction = &ctx.accounts.auction;
+        auction.ensure_resolvable(AuctionType::Discriminatory)?;
+
+        ctx.accounts.sign_pda_account.bump = ctx.bumps.sign_pda_account;
+
+        let args = ArgBuilder::new()
+            .plaintext_u8(auction.tie_break as u8)
+            .plaintext_u64(auction.total_units)
+            .plaintext_u128(ctx.accounts.auction_state.state_nonce)
+            .account(
+                ctx.accounts.auction_state.key(),
+                AuctionStateAccount::ENCRYPTED_STATE_OFFSET,
+                AuctionStateAccount::ENCRYPTED_STATE_SIZE,
+            )
+            .build();
+
+        ctx.accounts.auction.start_computation(
+            ComputationKind::Resolve,
+            ctx.accounts.computation_account.key(),
+        )?;
+
+        queue_computation(
+            ctx.accounts,
+            computation_offset,
+            args,
+            None,
+            vec![DiscriminatoryWinnersCallback::callback_ix(
+                computation_offset,
+                &ctx.accounts.mxe_account,
+                &[
+                    CallbackAccount {
+                        pubkey: ctx.accounts.auction.key(),
+                        is_writable: true,
+                    },
+                    CallbackAccount {
+                        pubkey: ctx.accounts.allocations.key(),
+                        is_writable: true,
+                    },
+                ],
+            )?],
+            1,
+            0,
+        )?;
+
+        Ok(())
+    }
+    #[arcium_callback(encrypted_ix = "discriminatory_winners")]
+    pub fn discriminatory_winners_callback(
+        ctx: Context<DiscriminatoryWinnersCallback>,
+        output: SignedComputationOutputs<DiscriminatoryWinnersOutput>,
+    ) -> Result<()> {
+        if !ctx
+            .accounts
+            .auction
+            .is_pending(&ctx.accounts.computation_account.key())
+        {
+            emit!(StaleComputationEvent {
+                auction: ctx.accounts.auction.key(),
+                bidder: None,
+                kind: ComputationKind::Resolve,
+                computation_account: ctx.accounts.computation_account.key(),
+            });
+            return Ok(());
+        }
+
+        let (winners_lo, winners_hi, quantities, prices, price_shared) = match output.verify_output(
+            &ctx.accounts.cluster_account,
+            &ctx.accounts.computation_account,
+        ) {
+            Ok(DiscriminatoryWinnersOutput {
+                field_0:
+                    DiscriminatoryWinnersOutputStruct0 {
+                        field_0: winners_lo,
+                        field_1: winners_hi,
+                        field_2: quantities,
+                        field_3: prices,
+                        field_4: price_shared,
+                    },
+            }) => (winners_lo, winners_hi, quantities, prices, price_shared),
+            Err(_) => {
+                let auction = &mut ctx.accounts.auction;
+                auction.fail_pending_computation();
+                emit!(ComputationFailedEvent {
+                    auction: auction.key(),
+                    kind: ComputationKind::Resolve,
+                    computation_account: ctx.accounts.computation_account.key(),
+                });
+                return Ok(());
+            }
+        };
+
+        // Winners are ranked by price, so the last filled slot holds the lowest
+        // accepted price, which is kept as the clearing price for reference.
+        let allocations = &mut ctx.accounts.allocations;
+        let mut units_sold: u64 = 0;
+        let mut total_payment: u64 = 0;
+        for (i, &quantity) in quantities.iter().enumerate() {
+            if quantity == 0 {
+                continue;
+            }
+            let payment = prices[i]
+                .checked_mul(quantity)
+                .ok_or(ErrorCode::MathOverflow)?;
+            allocations.allocations[i] = Allocation {
+                bidder: pubkey_from_halves(winners_lo[i], winners_hi[i]),
+                quantity,
+                payment,
+                claimed: false,
+            };
+            allocations.clearing_price = prices[i];
+            units_sold += quantity;
+            total_payment = total_payment
+                .checked_add(payment)
+                .ok_or(ErrorCode::MathOverflow)?;
+        }
+
+        let auction_key = ctx.accounts.auction.key();
+        ctx.accounts
+            .auction
+            .finish_resolution(&mut ctx.accounts.allocations)?;
+
+        emit!(DiscriminatoryResolvedEvent {
+            auction: auction_key,
+            units_sold,
+            total_payment,
+            price_shared,
+        });
+
+        Ok(())
+    }
+    /// Sends a bidder their own outcome, encrypted to the x25519 key they bid
+    /// with. The allocation and price are already public in the allocations
+    /// account; what this adds is the bid the auction recorded for them, which
+    /// is never revealed on-chain.
+    pub fn bidder_result(
+        ctx: Context<BidderResult>,
+        computation_offset: u64,
+        nonce: u128,
+    ) -> Result<()> {
+        require!(
+            ctx.accounts.auction.status == AuctionStatus::Resolved,
+            ErrorCode::AuctionNotResolved
+        );
+
+        let bidder = ctx.accounts.bidder.key();
+        let (allocated, payment) = ctx.accounts.allocations.allocation_of(&bidder)?;
+        let unit_price = if allocated > 0 { payment / allocated } else { 0 };
+        let (signer_lo, signer_hi) = pubkey_halves(&bidder);
+
+        ctx.accounts.sign_pda_account.bump = ctx.bumps.sign_pda_account;
+
+        let args = ArgBuilder::new()
+            .x25519_pubkey(ctx.accounts.receipt.encryption_pubkey)
+            .plaintext_u128(nonce)
+            .plaintext_u128(signer_lo)
+            .plaintext_u128(signer_hi)
+            .plaintext_u64(allocated)
+            .plaintext_u64(unit_price)
+            .plaintext_u128(ctx.accounts.auction_state.state_nonce)
+            .account(
+                ctx.accounts.auction_state.key(),
+                AuctionStateAccount::ENCRYPTED_STATE_OFFSET,
+                AuctionStateAccount::ENCRYPTED_STATE_SIZE,
+            )
+            .build();
+
+        queue_computation(
+            ctx.accounts,
+            computation_offset,
+            args,
+            None,
+            vec![BidderResultCallback::callback_ix(
+                computation_offset,
+                &ctx.accounts.mxe_account,
+                &[CallbackAccount {
+                    pubkey: ctx.accounts.receipt.key(),
+                    is_writable: false,
+                }],
+            )?],
+            1,
+            0,
+        )?;
+
+        Ok(())
+    }
+    #[arcium_callback(encrypted_ix = "bidder_result")]
+    pub fn bidder_result_callback(
+        ctx: Context<BidderResultCallback>,
+        output: SignedComputationOutputs<BidderResultOutput>,
+    ) -> Result<()> {
+        let o = match output.verify_output(
+            &ctx.accounts.cluster_account,
+            &ctx.accounts.computation_account,
+        ) {
+            Ok(BidderResultOutput { field_0 }) => field_0,
+            Err(_) => {
+                emit!(ComputationFailedEvent {
+                    auction: ctx.accounts.receipt.auction,
+                    kind: ComputationKind::BidderResult,
+                    computation_account: ctx.accounts.computation_account.key(),
+                });
+                return Ok(());
+            }
+        };
+
+        emit!(BidderResultEvent {
+            auction: ctx.accounts.receipt.auction,
+            bidder: ctx.accounts.receipt.bidder,
+            encryption_key: o.encryption_key,
+            nonce: o.nonce.to_le_bytes(),
+            ciphertexts: o.ciphertexts,
+        });
+
+        Ok(())
+    }
+    pub fn withdraw_deposit(ctx: Context<WithdrawDeposit>) -> Result<()> {
+        let auction = &ctx.accounts.auction;
+        require!(
+            auction.is_finalized(),
+            ErrorCode::AuctionNotFinalized
+        );
+
+        require!(!ctx.accounts.receipt.withdrawn, ErrorCode::AlreadyClaimed);
+
+        // Winners' payments stay in the quote vault for `claim_proceeds`, so a
+        // winner only gets back what they deposited above what they owe.
+        let bidder = ctx.accounts.receipt.bidder;
+        let charged = ctx.accounts.allocations.payment_owed_by(&bidder)?;
+        let refund = ctx
+            .accounts
+            .receipt
+            .deposit
+            .checked_sub(charged)
+            .ok_or(ErrorCode::MathOverflow)?;
+
+        if refund > 0 {
+            transfer_from_vault(
+                &ctx.accounts.vault,
+                &ctx.accounts.quote_vault,
+                &ctx.accounts.bidder_quote_account,
+                &ctx.accounts.quote_mint,
+                &ctx.accounts.quote_token_program,
+                refund,
+            )?;
+        }
+
+        ctx.accounts.receipt.withdrawn = true;
+
+        emit!(DepositWithdrawnEvent {
+            auction: auction.key(),
+            bidder,
+            amount: refund,
+        });
+
+        Ok(())
+    }
+    /// Closes a settled bid receipt and returns its rent to the bidder.
+    pub fn close_bid_receipt(ctx: Context<CloseBidReceipt>) -> Result<()> {
+        require!(
+            ctx.accounts.auction.is_finalized(),
+            ErrorCode::AuctionNotFinalized
+        );
+        require!(
+            ctx.accounts.receipt.withdrawn,
+            ErrorCode::DepositNotWithdrawn
+        );
+
+        emit!(BidReceiptClosedEvent {
+            auction: ctx.accounts.auction.key(),
+            bidder: ctx.accounts.receipt.bidder,
+        });
+
+        Ok(())
+    }
+    /// Hands a winner their share of the sale supply. Anyone can crank it once
+    /// the auction is resolved.
+    pub fn claim_allocation(ctx: Context<ClaimAllocation>, index: u8) -> Result<()> {
+        let auction = &ctx.accounts.auction;
+        require!(
+            auction.status == AuctionStatus::Resolved,
+            ErrorCode::AuctionNotResolved
+        );
+
+        let index = index as usize;
+        require!(index < MAX_WINNERS, ErrorCode::NoAllocation);
+        let allocation = ctx.accounts.allocations.allocations[index];
+        require!(allocation.quantity > 0, ErrorCode::NoAllocation);
+        require!(!allocation.claimed, ErrorCode::AlreadyClaimed);
+        require_keys_eq!(
+            ctx.accounts.recipient_sale_account.owner,
+            allocation.bidder,
+            ErrorCode::Unauthorized
+        );
+
+        let auction_key = auction.key();
+        let amount = auction
+            .unit_amount()
+            .checked_mul(allocation.quantity)
+            .ok_or(ErrorCode::MathOverflow)?;
+
+        transfer_from_vault(
+            &ctx.accounts.vault,
+            &ctx.accounts.sale_vault,
+            &ctx.accounts.recipient_sale_account,
+            &ctx.accounts.sale_mint,
+            &ctx.accounts.sale_token_program,
+            amount,
+        )?;
+
+        ctx.accounts.allocations.allocations[index].claimed = true;
+
+        emit!(AllocationClaimedEvent {
+            auction: auction_key,
+            bidder: allocation.bidder,
+            quantity: allocation.quantity,
+            amount,
+        });
+
+        Ok(())
+    }
+    /// Sends the winners' payments, plus any supply left unsold, to the authority.
+    /// Anyone can crank it once the auction is resolved.
+    pub fn claim_proceeds(ctx: Context<ClaimProceeds>) -> Result<()> {
+        let auction = &ctx.accounts.auction;
+        require!(
+            auction.is_finalized(),
+            ErrorCode::AuctionNotFinalized
+        );
+        require!(!auction.proceeds_claimed, ErrorCode::AlreadyClaimed);
+
+        let allocations = &ctx.accounts.allocations;
+        let payment_amount = allocations.total_payment()?;
+        let sold_amount = auction
+            .unit_amount()
+            .checked_mul(allocations.units_sold())
+            .ok_or(ErrorCode::MathOverflow)?;
+        let unsold_amount = auction
+            .sale_amount
+            .checked_sub(sold_amount)
+            .ok_or(ErrorCode::MathOverflow)?;
+        let auction_key = auction.key();
+
+        if payment_amount > 0 {
+            transfer_from_vault(
+                &ctx.accounts.vault,
+                &ctx.accounts.quote_vault,
+                &ctx.accounts.authority_quote_account,
+                &ctx.accounts.quote_mint,
+                &ctx.accounts.quote_token_program,
+                payment_amount,
+            )?;
+        }
+        if unsold_amount > 0 {
+            transfer_from_vault(
+                &ctx.accounts.vault,
+                &ctx.accounts.sale_vault,
+                &ctx.accounts.authority_sale_account,
+                &ctx.accounts.sale_mint,
+                &ctx.accounts.sale_token_program,
+                unsold_amount,
+            )?;
+        }
+
+        ctx.accounts.auction.proceeds_claimed = true;
+
+        emit!(ProceedsClaimedEvent {
+            auction: auction_key,
+            payment_amount,
+            unsold_amount,
+        });
+
+        Ok(())
+    }
+}
+
+/// Checks a Merkle proof that `(bidder, max_allocation)` is a leaf under `root`.
+/// Leaves are `sha256(bidder || max_allocation_le)`, and each node hashes its two
+/// children in sorted order, so a proof is just the list of sibling hashes.
+fn allowlist_contains(
+    root: &[u8; 32],
+    bidder: &Pubkey,
+    max_allocation: u64,
+    proof: &[[u8; 32]],
+) -> bool {
+    let mut node = hashv(&[bidder.as_ref(), &max_allocation.to_le_bytes()]).to_bytes();
+    for sibling in proof {
+        node = if node <= *sibling {
+            hashv(&[node.as_ref(), sibling.as_ref()]).to_bytes()
+        } else {
+            hashv(&[sibling.as_ref(), node.as_ref()]).to_bytes()
+        };
+    }
+    node == *root
+}
+
+/// Splits a pubkey into the two little-endian u128 halves the circuits work with.
+fn pubkey_halves(key: &Pubkey) -> (u128, u128) {
+    let bytes = key.to_bytes();
+    let mut lo = [0u8; 16];
+    let mut hi = [0u8; 16];
+    lo.copy_from_slice(&bytes[..16]);
+    hi.copy_from_slice(&bytes[16..]);
+    (u128::from_le_bytes(lo), u128::from_le_bytes(hi))
+}
+
+/// The wrapped SOL mint of either token program.
+fn is_native_mint(mint: &Pubkey) -> bool {
+    *mint == anchor_spl::token::spl_token::native_mint::ID
+        || *mint == anchor_spl::token_2022::spl_token_2022::native_mint::ID
+}
+
+/// Circuit arguments for the bid slots of a `process_bids` computation. Slots
+/// past `entries` repeat the first bid's ciphertexts so the circuit always gets
+/// well-formed input; it ignores them.
+fn bid_batch_args(entries: &[QueuedBid]) -> ArgBuilder {
+    let mut args = ArgBuilder::new();
+    for i in 0..BID_BATCH_SIZE {
+        let (entry, present) = match entries.get(i) {
+            Some(entry) => (entry, true),
+            None => (&entries[0], false),
+        };
+        let (signer_lo, signer_hi) = if present {
+            pubkey_halves(&entry.bidder)
+        } else {
+            (0, 0)
+        };
+        args = args
+            .x25519_pubkey(entry.encryption_pubkey)
+            .plaintext_u128(entry.nonce)
+            .encrypted_u64(entry.ciphertexts[0])
+            .encrypted_u64(entry.ciphertexts[1])
+            .encrypted_u64(entry.ciphertexts[2])
+            .plaintext_u128(signer_lo)
+            .plaintext_u128(signer_hi)
+            .plaintext_u64(if present { entry.deposit } else { 0 })
+            .plaintext_u64(if present { entry.max_units } else { 0 });
+    }
+    args
+}
+
+/// Reassembles a bidder pubkey from the two u128 halves the circuits work with.
+fn pubkey_from_halves(lo: u128, hi: u128) -> Pubkey {
+    let mut bytes = [0u8; 32];
+    bytes[..16].copy_from_slice(&lo.to_le_bytes());
+    bytes[16..].copy_from_slice(&hi.to_le_bytes());
+    Pubkey::new_from_array(bytes)
+}
+
+/// Moves `amount` out of one of the auction's token vaults, signed by the vault PDA.
+fn transfer_from_vault<'info>(
+    vault: &Account<'info, AuctionVault>,
+    from: &InterfaceAccount<'info, TokenAccount>,
+    to: &InterfaceAccount<'info, TokenAccount>,
+    mint: &InterfaceAccount<'info, Mint>,
+    token_program: &Interface<'info, TokenInterface>,
+    amount: u64,
+) -> Result<()> {
+    let seeds = &[b"vault".as_ref(), vault.auction.as_ref(), &[vault.bump]];
+    let signer_seeds = &[&seeds[..]];
+
+    token_interface::transfer_checked(
+        CpiContext::new_with_signer(
+            token_program.to_account_info(),
+            TransferChecked {
+                from: from.to_account_info(),
+                mint: mint.to_account_info(),
+                to: to.to_account_info(),
+                authority: vault.to_account_info(),
+            },
+            signer_seeds,
+        ),
+        amount,
+        mint.decimals,
+    )
+}
+#[derive(Accounts)]
+pub struct CloseAuction<'info> {
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [b"auction".as_ref(), auction.authority.as_ref(), &auction.auction_id.to_le_bytes()],
+        bump = auction.bump,
+        has_one = authority @ ErrorCode::Unauthorized,
+    )]
+    pub auction: Account<'info, Auction>,
+}
+#[derive(Accounts)]
+pub struct CancelAuction<'info> {
+    pub authority: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [b"auction".as_ref(), auction.authority.as_ref(), &auction.auction_id.to_le_bytes()],
+        bump = auction.bump,
+        has_one = authority @ ErrorCode::Unauthorized,
+    )]
+    pub auction: Account<'info, Auction>,
+}
+#[derive(Accounts)]
+pub struct SetAllowlistRoot<'info> {
+    pub authority: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [b"auction".as_ref(), auction.authority.as_ref(), &auction.auction_id.to_le_bytes()],
+        bump = auction.bump,
+        has_one = authority @ ErrorCode::Unauthorized,
+    )]
+    pub auction: Account<'info, Auction>,
+}
+#[derive(Accounts)]
+pub struct CloseExpiredAuction<'info> {
+    pub caller: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [b"auction".as_ref(), auction.authority.as_ref(), &auction.auction_id.to_le_bytes()],
+        bump = auction.bump,
+    )]
+    pub auction: Account<'info, Auction>,
+}
+
+#[account]
+#[derive(InitSpace)]
+pub struct Auction{
+    pub bump: u8,
+    pub authority: Pubkey,
+    pub auction_id: u64,
+    pub auction_type: AuctionType,
+    pub min_bid: u64,
+    pub end_time: i64,
+    pub status: AuctionStatus,
+    pub bid_count: u32,
+    pub sale_mint: Pubkey,
+    pub quote_mint: Pubkey,
+    pub sale_vault: Pubkey,
+    pub quote_vault: Pubkey,
+    pub sale_amount: u64,
+    pub total_units: u64,
+    pub proceeds_claimed: bool,
+    pub tie_break: TieBreak,
+    /// Bids accepted before `place_bid` starts rejecting new ones.
+    pub max_bids: u32,
+    /// Whether a second `place_bid` from the same signer replaces their bid
+    /// instead of failing with `DuplicateBid`.
+    pub allow_bid_revision: bool,
+    /// Merkle root of approved bidders and their unit caps; `None` lets anyone bid.
+    pub allowlist_root: Option<[u8; 32]>,
+    /// Most quote tokens any single bid may commit; enforced inside the circuit.
+    /// Zero means no cap.
+    pub max_bid_spend: u64,
+    pub pending_computation: Option<PendingComputation>,
+    /// Queued bids and in-flight cancellations that have not reached the
+    /// auction state yet. Resolution waits for this to drain.
+    pub bids_in_flight: u32,
+    /// A ranked bid was revised or cancelled while more bids were active than the
+    /// ranking holds, so a bid pushed out earlier may belong back in. Resolution
+    /// waits for `rerank_bids` to rebuild the ranking.
+    pub rerank_required: bool,
+}
+impl Auction {
+    /// Sale tokens backing one unit. The remainder of the division stays with
+    /// the authority and is returned by `claim_proceeds`.
+    pub fn unit_amount(&self) -> u64 {
+        self.sale_amount / self.total_units
+    }
+
+    pub fn start_computation(
+        &mut self,
+        kind: ComputationKind,
+        computation_account: Pubkey,
+    ) -> Result<()> {
+        self.pending_computation = Some(PendingComputation {
+            kind,
+            computation_account,
+            queued_at: Clock::get()?.unix_timestamp,
+            failed: false,
+        });
+        Ok(())
+    }
+
+    /// The pending computation of this kind aborted, or its callback has not
+    /// landed within `COMPUTATION_TIMEOUT`.
+    pub fn can_retry(&self, kind: ComputationKind, now: i64) -> bool {
+        matches!(
+            self.pending_computation,
+            Some(pending) if pending.kind == kind
+                && (pending.failed || now >= pending.queued_at.saturating_add(COMPUTATION_TIMEOUT))
+        )
+    }
+
+    /// A retried computation can still call back late; only the one the
+    /// auction is waiting on may apply its result.
+    pub fn is_pending(&self, computation_account: &Pubkey) -> bool {
+        matches!(
+            self.pending_computation,
+            Some(pending) if pending.computation_account == *computation_account
+        )
+    }
+
+    /// Called when a bid leaves the ranking through a revision or a cancellation.
+    pub fn note_ranked_bid_removed(&mut self) {
+        if self.bid_count as usize > MAX_WINNERS {
+            self.rerank_required = true;
+        }
+    }
+
+    pub fn fail_pending_computation(&mut self) {
+        if let Some(pending) = self.pending_computation.as_mut() {
+            pending.failed = true;
+        }
+    }
+
+    /// Resolved or cancelled: deposits can be withdrawn and proceeds claimed.
+    pub fn is_finalized(&self) -> bool {
+        matches!(
+            self.status,
+            AuctionStatus::Resolved | AuctionStatus::Cancelled
+        )
+    }
+    /// Checks shared by every resolve instruction. Every queued bid and
+    /// cancellation must be in the state being resolved, and an aborted or
+    /// timed-out resolution is retried by calling the resolve instruction again.
+    pub fn ensure_resolvable(&self, auction_type: AuctionType) -> Result<()> {
+        require!(
+            self.status == AuctionStatus::Closed,
+            ErrorCode::AuctionNotClosed
+        );
+        require!(
+            self.auction_type == auction_type,
+            ErrorCode::WrongAuctionType
+        );
+        require!(self.bids_in_flight == 0, ErrorCode::BidsInFlight);
+        require!(!self.rerank_required, ErrorCode::RerankRequired);
+        require!(
+            self.pending_computation.is_none()
+                || self.can_retry(ComputationKind::Resolve, Clock::get()?.unix_timestamp),
+            ErrorCode::ComputationPending
+        );
+        Ok(())
+    }
+
+    /// Marks the auction resolved once a resolve callback has filled in the
+    /// allocations. A cancellation that landed while the computation was
+    /// queued wins.
+    pub fn finish_resolution(&mut self, allocations: &mut AuctionAllocations) -> Result<()> {
+        require!(
+            self.status == AuctionStatus::Closed,
+            ErrorCode::AuctionNotClosed
+        );
+        self.status = AuctionStatus::Resolved;
+        self.pending_computation = None;
+        allocations.mark_resolved(Clock::get()?.unix_timestamp);
+        Ok(())
+    }
+}
+/// MXE-encrypted bid state, kept apart from `Auction` so a richer circuit state
+/// only has to grow this account.
+#[account]
+#[derive(InitSpace)]
+pub struct AuctionStateAccount {
+    pub bump: u8,
+    pub auction: Pubkey,
+    pub state_nonce: u128,
+    /// Bumped on every write, so a computation queued against an older state
+    /// can be recognised when its callback lands.
+    pub state_version: u64,
+    /// Must stay the last field, since the circuits read it by byte offset.
+    #[max_len(ENCRYPTED_STATE_FIELDS)]
+    pub encrypted_state: Vec<[u8; 32]>,
+}
+impl AuctionStateAccount {
+    /// Byte offset of the first ciphertext: the discriminator, the fields before
+    /// `encrypted_state` and its length prefix.
+    pub const ENCRYPTED_STATE_OFFSET: u32 = (8
+        + size_of::<u8>() // bump
+        + size_of::<Pubkey>() // auction
+        + size_of::<u128>() // state_nonce
+        + size_of::<u64>() // state_version
+        + size_of::<u32>()) as u32; // vec length prefix
+    pub const ENCRYPTED_STATE_SIZE: u32 = 32 * ENCRYPTED_STATE_FIELDS as u32;
+
+    pub fn write(&mut self, ciphertexts: &[[u8; 32]], nonce: u128) {
+        self.encrypted_state = ciphertexts.to_vec();
+        self.state_nonce = nonce;
+        self.state_version += 1;
+    }
+}
+// Fails to compile if a field is added after `encrypted_state` or the offset
+// above falls out of step with the struct.
+const _: () = assert!(
+    AuctionStateAccount::ENCRYPTED_STATE_OFFSET as usize
+        + AuctionStateAccount::ENCRYPTED_STATE_SIZE as usize
+        == 8 + AuctionStateAccount::INIT_SPACE,
+    "AuctionStateAccount layout changed: update ENCRYPTED_STATE_OFFSET"
+);
+/// Units and payment owed for one winning bid.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Default, InitSpace)]
+pub struct Allocation {
+    pub bidder: Pubkey,
+    pub quantity: u64,
+    pub payment: u64,
+    pub claimed: bool,
+}
+/// Resolution output that settlement, claims and other programs read from.
+/// Single-lot auctions use the first slot only; empty slots have a zero
+/// quantity. In a discriminatory auction `clearing_price` is the lowest accepted
+/// price, not what everyone pays.
+#[account]
+#[derive(InitSpace)]
+pub struct AuctionAllocations {
+    pub bump: u8,
+    pub auction: Pubkey,
+    pub clearing_price: u64,
+    pub allocations: [Allocation; MAX_WINNERS],
+    /// The highest-ranked bidder that received units, or the default pubkey
+    /// if nothing was sold.
+    pub winner: Pubkey,
+    /// Unix timestamp of the resolution; zero until the auction is resolved.
+    pub resolved_at: i64,
+}
+impl AuctionAllocations {
+    /// Records the sole winner of a single-lot auction. Without a valid bid the
+    /// winner is the default pubkey and nothing is allocated.
+    pub fn record_single_winner(&mut self, winner: Pubkey, quantity: u64, payment: u64) {
+        self.clearing_price = payment;
+        if winner != Pubkey::default() {
+            self.allocations[0] = Allocation {
+                bidder: winner,
+                quantity,
+                payment,
+                claimed: false,
+            };
+        }
+    }
+
+    pub fn mark_resolved(&mut self, now: i64) {
+        self.winner = self
+            .allocations
+            .iter()
+            .find(|a| a.quantity > 0)
+            .map_or(Pubkey::default(), |a| a.bidder);
+        self.resolved_at = now;
+    }
+
+    pub fn units_sold(&self) -> u64 {
+        self.allocations.iter().map(|a| a.quantity).sum()
+    }
+
+    pub fn total_payment(&self) -> Result<u64> {
+        self.allocations.iter().try_fold(0u64, |total, a| {
+            total
+                .checked_add(a.payment)
+                .ok_or_else(|| ErrorCode::MathOverflow.into())
+        })
+    }
+
+    /// Units and payment summed over every slot a bidder holds.
+    pub fn allocation_of(&self, bidder: &Pubkey) -> Result<(u64, u64)> {
+        let quantity = self
+            .allocations
+            .iter()
+            .filter(|a| a.quantity > 0 && a.bidder == *bidder)
+            .map(|a| a.quantity)
+            .sum();
+        Ok((quantity, self.payment_owed_by(bidder)?))
+    }
+
+    /// A bidder can hold several slots if they bid more than once.
+    pub fn payment_owed_by(&self, bidder: &Pubkey) -> Result<u64> {
+        self.allocations
+            .iter()
+            .filter(|a| a.quantity > 0 && a.bidder == *bidder)
+            .try_fold(0u64, |total, a| {
+                total
+                    .checked_add(a.payment)
+                    .ok_or_else(|| ErrorCode::MathOverflow.into())
+            })
+    }
+}
+/// Signs for the auction's sale and quote token vaults.
+#[account]
+#[derive(InitSpace)]
+pub struct AuctionVault {
+    pub bump: u8,
+    pub auction: Pubkey,
+}
+/// A bid waiting to be folded into the auction state, exactly as `place_bid`
+/// received it.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, InitSpace)]
+pub struct QueuedBid {
+    pub bidder: Pubkey,
+    pub encryption_pubkey: [u8; 32],
+    pub nonce: u128,
+    /// Encrypted amount, quantity and maximum spend.
+    pub ciphertexts: [[u8; 32]; 3],
+    /// The bidder's total deposit when the bid was placed.
+    pub deposit: u64,
+    pub max_units: u64,
+}
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, InitSpace)]
+pub struct PendingBatch {
+    pub computation_offset: u64,
+    /// Only a callback from this computation account may apply the batch.
+    pub computation_account: Pubkey,
+    /// When the batch was queued; `expire_bid_batch` measures the timeout from here.
+    pub queued_at: i64,
+    pub count: u8,
+    pub state_version: u64,
+    /// Set for a `rerank_bids` batch: where the pass continues once it lands.
+    pub rerank_from: Option<u64>,
+}
+/// Bids placed but not yet folded into the auction state, oldest first.
+/// `place_bid` appends to it and `process_bids` drains it in batches.
+#[account]
+#[derive(InitSpace)]
+pub struct BidQueue {
+    pub bump: u8,
+    pub auction: Pubkey,
+    /// Bids ever queued; the next bid gets this sequence number.
+    pub next_seq: u64,
+    /// Bids folded into the auction state so far.
+    pub processed_seq: u64,
+    pub in_flight: Option<PendingBatch>,
+    /// Lowest `queue_seq` the next `rerank_bids` batch may start at.
+    pub rerank_from: u64,
+    /// Active bids folded into the rebuilt ranking so far.
+    pub reranked: u32,
+    #[max_len(BID_QUEUE_CAPACITY)]
+    pub entries: Vec<QueuedBid>,
+}
+impl BidQueue {
+    pub fn is_processed(&self, seq: u64) -> bool {
+        seq < self.processed_seq
+    }
+}
+/// On-chain proof that a bidder took part, and the running total of quote
+/// tokens they have deposited into the quote vault. The other fields describe
+/// their latest bid.
+#[account]
+#[derive(InitSpace)]
+pub struct BidReceipt {
+    pub bump: u8,
+    pub auction: Pubkey,
+    pub bidder: Pubkey,
+    pub deposit: u64,
+    /// The `process_bids` computation that folded the latest bid into the
+    /// auction state; zero until then.
+    pub computation_offset: u64,
+    /// Position of the latest bid in the auction's bid queue. It has landed in
+    /// the auction state once `BidQueue::is_processed` says so.
+    pub queue_seq: u64,
+    pub placed_at: i64,
+    /// Set by the `process_bids` callback once the latest bid has landed in the
+    /// auction state.
+    pub confirmed: bool,
+    /// A `cancel_bid` computation has been queued and not finished.
+    pub pending: bool,
+    /// Auction state version the pending cancellation was queued against.
+    pub state_version: u64,
+    /// Computation of the pending cancellation; a late callback from one that
+    /// was expired is ignored.
+    pub cancel_computation_account: Pubkey,
+    /// When the pending cancellation was queued, for `expire_cancel_bid`.
+    pub cancel_queued_at: i64,
+    /// The bidder has a bid in the auction state or the bid queue; cleared by `cancel_bid`.
+    pub active: bool,
+    /// x25519 key of the latest bid, which `bidder_result` encrypts to.
+    pub encryption_pubkey: [u8; 32],
+    /// The latest bid as it was queued, kept so `rerank_bids` can fold it in again.
+    pub nonce: u128,
+    pub ciphertexts: [[u8; 32]; 3],
+    pub max_units: u64,
+    pub withdrawn: bool,
+}
+impl BidReceipt {
+    pub fn queued_bid(&self) -> QueuedBid {
+        QueuedBid {
+            bidder: self.bidder,
+            encryption_pubkey: self.encryption_pubkey,
+            nonce: self.nonce,
+            ciphertexts: self.ciphertexts,
+            deposit: self.deposit,
+            max_units: self.max_units,
+        }
+    }
+
+
+    /// The bidder has a bid in the auction state or one still being computed.
+    /// A cancelled bid no longer counts.
+    pub fn has_bid(&self) -> bool {
+        self.active || self.pending
+    }
+}
+#[derive(Accounts)]
+pub struct WithdrawDeposit<'info> {
+    #[account(mut)]
+    pub bidder: Signer<'info>,
+    #[account(
+        seeds = [b"auction".as_ref(), auction.authority.as_ref(), &auction.auction_id.to_le_bytes()],
+        bump = auction.bump,
+    )]
+    pub auction: Account<'info, Auction>,
+    #[account(
+        seeds = [b"vault".as_ref(), auction.key().as_ref()],
+        bump = vault.bump,
+    )]
+    pub vault: Account<'info, AuctionVault>,
+    #[account(
+        seeds = [b"allocations".as_ref(), auction.key().as_ref()],
+        bump = allocations.bump,
+    )]
+    pub allocations: Box<Account<'info, AuctionAllocations>>,
+    #[account(
+        mut,
+        seeds = [b"receipt".as_ref(), auction.key().as_ref(), bidder.key().as_ref()],
+        bump = receipt.bump,
+        has_one = bidder @ ErrorCode::Unauthorized,
+    )]
+    pub receipt: Account<'info, BidReceipt>,
+    #[account(address = auction.quote_mint @ ErrorCode::InvalidMint)]
+    pub quote_mint: Box<InterfaceAccount<'info, Mint>>,
+    #[account(mut, address = auction.quote_vault @ ErrorCode::InvalidVault)]
+    pub quote_vault: Box<InterfaceAccount<'info, TokenAccount>>,
+    #[account(
+        mut,
+        token::mint = quote_mint,
+        token::authority = bidder,
+        token::token_program = quote_token_program,
+    )]
+    pub bidder_quote_account: Box<InterfaceAccount<'info, TokenAccount>>,
+    pub quote_token_program: Interface<'info, TokenInterface>,
+}
+#[derive(Accounts)]
+pub struct CloseBidReceipt<'info> {
+    #[account(mut)]
+    pub bidder: Signer<'info>,
+    #[account(
+        seeds = [b"auction".as_ref(), auction.authority.as_ref(), &auction.auction_id.to_le_bytes()],
+        bump = auction.bump,
+    )]
+    pub auction: Account<'info, Auction>,
+    #[account(
+        mut,
+        seeds = [b"receipt".as_ref(), auction.key().as_ref(), bidder.key().as_ref()],
+        bump = receipt.bump,
+        has_one = bidder @ ErrorCode::Unauthorized,
+        close = bidder,
+    )]
+    pub receipt: Account<'info, BidReceipt>,
+}
+#[derive(Accounts)]
+pub struct ClaimAllocation<'info> {
+    pub payer: Signer<'info>,
+    #[account(
+        seeds = [b"auction".as_ref(), auction.authority.as_ref(), &auction.auction_id.to_le_bytes()],
+        bump = auction.bump,
+    )]
+    pub auction: Box<Account<'info, Auction>>,
+    #[account(
+        seeds = [b"vault".as_ref(), auction.key().as_ref()],
+        bump = vault.bump,
+    )]
+    pub vault: Account<'info, AuctionVault>,
+    #[account(
+        mut,
+        seeds = [b"allocations".as_ref(), auction.key().as_ref()],
+        bump = allocations.bump,
+    )]
+    pub allocations: Box<Account<'info, AuctionAllocations>>,
+    #[account(address = auction.sale_mint @ ErrorCode::InvalidMint)]
+    pub sale_mint: Box<InterfaceAccount<'info, Mint>>,
+    #[account(mut, address = auction.sale_vault @ ErrorCode::InvalidVault)]
+    pub sale_vault: Box<InterfaceAccount<'info, TokenAccount>>,
+    #[account(
+        mut,
+        token::mint = sale_mint,
+        token::token_program = sale_token_program,
+    )]
+    pub recipient_sale_account: Box<InterfaceAccount<'info, TokenAccount>>,
+    pub sale_token_program: Interface<'info, TokenInterface>,
+}
+#[derive(Accounts)]
+pub struct ClaimProceeds<'info> {
+    pub payer: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [b"auction".as_ref(), auction.authority.as_ref(), &auction.auction_id.to_le_bytes()],
+        bump = auction.bump,
+    )]
+    pub auction: Box<Account<'info, Auction>>,
+    #[account(
+        seeds = [b"vault".as_ref(), auction.key().as_ref()],
+        bump = vault.bump,
+    )]
+    pub vault: Account<'info, AuctionVault>,
+    #[account(
+        seeds = [b"allocations".as_ref(), auction.key().as_ref()],
+        bump = allocations.bump,
+    )]
+    pub allocations: Box<Account<'info, AuctionAllocations>>,
+    #[account(address = auction.sale_mint @ ErrorCode::InvalidMint)]
+    pub sale_mint: Box<InterfaceAccount<'info, Mint>>,
+    #[account(address = auction.quote_mint @ ErrorCode::InvalidMint)]
+    pub quote_mint: Box<InterfaceAccount<'info, Mint>>,
+    #[account(mut, address = auction.sale_vault @ ErrorCode::InvalidVault)]
+    pub sale_vault: Box<InterfaceAccount<'info, TokenAccount>>,
+    #[account(mut, address = auction.quote_vault @ ErrorCode::InvalidVault)]
+    pub quote_vault: Box<InterfaceAccount<'info, TokenAccount>>,
+    #[account(
+        mut,
+        token::mint = sale_mint,
+        token::authority = auction.authority,
+        token::token_program = sale_token_program,
+    )]
+    pub authority_sale_account: Box<InterfaceAccount<'info, TokenAccount>>,
+    #[account(
+        mut,
+        token::mint = quote_mint,
+        token::authority = auction.authority,
+        token::token_program = quote_token_program,
+    )]
+    pub authority_quote_account: Box<InterfaceAccount<'info, TokenAccount>>,
+    pub sale_token_program: Interface<'info, TokenInterface>,
+    pub quote_token_program: Interface<'info, TokenInterface>,
+}
+#[queue_computation_accounts("init_auction_state", authority)]
+#[derive(Accounts)]
+#[instruction(computation_offset: u64, auction_id: u64)]
+pub struct InitAuctionState<'info> {
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    #[account(
+        init,
+        payer = authority,
+        space = 8 + Auction::INIT_SPACE,
+        seeds = [b"auction".as_ref(), authority.key().as_ref(), &auction_id.to_le_bytes()],
+        bump,
+    )]
+    pub auction: Account<'info, Auction>,
+    #[account(
+        init,
+        payer = authority,
+        space = 8 + AuctionStateAccount::INIT_SPACE,
+        seeds = [b"auction_state".as_ref(), auction.key().as_ref()],
+        bump,
+    )]
+    pub auction_state: Box<Account<'info, AuctionStateAccount>>,
+    #[account(
+        init,
+        payer = authority,
+        space = 8 + BidQueue::INIT_SPACE,
+        seeds = [b"bid_queue".as_ref(), auction.key().as_ref()],
+        bump,
+    )]
+    pub bid_queue: Box<Account<'info, BidQueue>>,
+    #[account(
+        init,
+        payer = authority,
+        space = 8 + AuctionVault::INIT_SPACE,
+        seeds = [b"vault".as_ref(), auction.key().as_ref()],
+        bump,
+    )]
+    pub vault: Account<'info, AuctionVault>,
+    #[account(
+        init,
+        payer = authority,
+        space = 8 + AuctionAllocations::INIT_SPACE,
+        seeds = [b"allocations".as_ref(), auction.key().as_ref()],
+        bump,
+    )]
+    pub allocations: Box<Account<'info, AuctionAllocations>>,
+    #[account(mint::token_program = sale_token_program)]
+    pub sale_mint: Box<InterfaceAccount<'info, Mint>>,
+    #[account(mint::token_program = quote_token_program)]
+    pub quote_mint: Box<InterfaceAccount<'info, Mint>>,
+    #[account(
+        init,
+        payer = authority,
+        seeds = [b"sale_vault".as_ref(), auction.key().as_ref()],
+        bump,
+        token::mint = sale_mint,
+        token::authority = vault,
+        token::token_program = sale_token_program,
+    )]
+    pub sale_vault: Box<InterfaceAccount<'info, TokenAccount>>,
+    #[account(
+        init,
+        payer = authority,
+        seeds = [b"quote_vault".as_ref(), auction.key().as_ref()],
+        bump,
+        token::mint = quote_mint,
+        token::authority = vault,
+        token::token_program = quote_token_program,
+    )]
+    pub quote_vault: Box<InterfaceAccount<'info, TokenAccount>>,
+    #[account(
+        mut,
+        token::mint = sale_mint,
+        token::authority = authority,
+        token::token_program = sale_token_program,
+    )]
+    pub authority_sale_account: Box<InterfaceAccount<'info, TokenAccount>>,
+    pub sale_token_program: Interface<'info, TokenInterface>,
+    pub quote_token_program: Interface<'info, TokenInterface>,
+    #[account(
+        init_if_needed,
+        space = 9,
+        payer = authority,
+        seeds = [&SIGN_PDA_SEED],
+        bump,
+        address = derive_sign_pda!(),
+    )]
+    pub sign_pda_account: Account<'info, SignerAccount>,
+    #[account(address = derive_mxe_pda!())]
+    pub mxe_account: Account<'info, MXEAccount>,
+    #[account(mut, address = derive_mempool_pda!(mxe_account, ErrorCode::ClusterNotSet))]
+    /// CHECK: mempool_account, checked by the arcium program.
+    pub mempool_account: UncheckedAccount<'info>,
+    #[account(mut, address = derive_execpool_pda!(mxe_account, ErrorCode::ClusterNotSet))]
+    /// CHECK: executing_pool, checked by the arcium program.
+    pub executing_pool: UncheckedAccount<'info>,
+    #[account(mut, address = derive_comp_pda!(computation_offset, mxe_account, ErrorCode::ClusterNotSet))]
+    /// CHECK: computation_account, checked by the arcium program.
+    pub computation_account: UncheckedAccount<'info>,
+    #[account(address = derive_comp_def_pda!(COMP_DEF_OFFSET_INIT_AUCTION_STATE))]
+    pub comp_def_account: Account<'info, ComputationDefinitionAccount>,
+    #[account(mut, address = derive_cluster_pda!(mxe_account, ErrorCode::ClusterNotSet))]
+    pub cluster_account: Account<'info, Cluster>,
+    #[account(mut, address = ARCIUM_FEE_POOL_ACCOUNT_ADDRESS)]
+    pub pool_account: Account<'info, FeePool>,
+    #[account(address = ARCIUM_CLOCK_ACCOUNT_ADDRESS)]
+    pub clock_account: Account<'info, ClockAccount>,
+    pub system_program: Program<'info, System>,
+    pub arcium_program: Program<'info, Arcium>,
+}
+
+#[queue_computation_accounts("init_auction_state", authority)]
+#[derive(Accounts)]
+#[instruction(computation_offset: u64)]
+pub struct RetryInitAuctionState<'info> {
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [b"auction".as_ref(), auction.authority.as_ref(), &auction.auction_id.to_le_bytes()],
+        bump = auction.bump,
+        has_one = authority @ ErrorCode::Unauthorized,
+    )]
+    pub auction: Account<'info, Auction>,
+    #[account(
+        seeds = [b"auction_state".as_ref(), auction.key().as_ref()],
+        bump = auction_state.bump,
+    )]
+    pub auction_state: Box<Account<'info, AuctionStateAccount>>,
+    #[account(
+        init_if_needed,
+        space = 9,
+        payer = authority,
+        seeds = [&SIGN_PDA_SEED],
+        bump,
+        address = derive_sign_pda!(),
+    )]
+    pub sign_pda_account: Account<'info, SignerAccount>,
+    #[account(address = derive_mxe_pda!())]
+    pub mxe_account: Account<'info, MXEAccount>,
+    #[account(mut, address = derive_mempool_pda!(mxe_account, ErrorCode::ClusterNotSet))]
+    /// CHECK: mempool_account, checked by the arcium program.
+    pub mempool_account: UncheckedAccount<'info>,
+    #[account(mut, address = derive_execpool_pda!(mxe_account, ErrorCode::ClusterNotSet))]
+    /// CHECK: executing_pool, checked by the arcium program.
+    pub executing_pool: UncheckedAccount<'info>,
+    #[account(mut, address = derive_comp_pda!(computation_offset, mxe_account, ErrorCode::ClusterNotSet))]
+    /// CHECK: computation_account, checked by the arcium program.
+    pub computation_account: UncheckedAccount<'info>,
+    #[account(address = derive_comp_def_pda!(COMP_DEF_OFFSET_INIT_AUCTION_STATE))]
+    pub comp_def_account: Account<'info, ComputationDefinitionAccount>,
+    #[account(mut, address = derive_cluster_pda!(mxe_account, ErrorCode::ClusterNotSet))]
+    pub cluster_account: Account<'info, Cluster>,
+    #[account(mut, address = ARCIUM_FEE_POOL_ACCOUNT_ADDRESS)]
+    pub pool_account: Account<'info, FeePool>,
+    #[account(address = ARCIUM_CLOCK_ACCOUNT_ADDRESS)]
+    pub clock_account: Account<'info, ClockAccount>,
+    pub system_program: Program<'info, System>,
+    pub arcium_program: Program<'info, Arcium>,
+}
+
+#[callback_accounts("init_auction_state")]
+#[derive(Accounts)]
+pub struct InitAuctionStateCallback<'info> {
+    pub arcium_program: Program<'info, Arcium>,
+    #[account(address = derive_comp_def_pda!(COMP_DEF_OFFSET_INIT_AUCTION_STATE))]
+    pub comp_def_account: Account<'info, ComputationDefinitionAccount>,
+    #[account(address = derive_mxe_pda!())]
+    pub mxe_account: Account<'info, MXEAccount>,
+    /// CHECK: computation_account, checked by arcium program via constraints in the callback context.
+    pub computation_account: UncheckedAccount<'info>,
+    #[account(address = derive_cluster_pda!(mxe_account, ErrorCode::ClusterNotSet))]
+    pub cluster_account: Account<'info, Cluster>,
+    #[account(address = ::anchor_lang::solana_program::sysvar::instructions::ID)]
+    /// CHECK: instructions_sysvar, checked by the account constraint
+    pub instructions_sysvar: AccountInfo<'info>,
+    #[account(
+        mut,
+        seeds = [b"auction".as_ref(), auction.authority.as_ref(), &auction.auction_id.to_le_bytes()],
+        bump = auction.bump,
+    )]
+    pub auction: Account<'info, Auction>,
+    #[account(
+        mut,
+        seeds = [b"auction_state".as_ref(), auction.key().as_ref()],
+        bump = auction_state.bump,
+    )]
+    pub auction_state: Box<Account<'info, AuctionStateAccount>>,
+}
+#[init_computation_definition_accounts("init_auction_state", payer)]
+#[derive(Accounts)]
+pub struct InitAuctionStateCompDef<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    #[account(mut, address = derive_mxe_pda!())]
+    pub mxe_account: Box<Account<'info, MXEAccount>>,
+    #[account(mut)]
+    /// CHECK: comp_def_account, checked by arcium program.
+    pub comp_def_account: UncheckedAccount<'info>,
+    pub arcium_program: Program<'info, Arcium>,
+    pub system_program: Program<'info, System>,
+}
+#[init_computation_definition_accounts("process_bids", payer)]
+#[derive(Accounts)]
+pub struct InitProcessBidsCompDef<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    #[account(mut, address = derive_mxe_pda!())]
+    pub mxe_account: Box<Account<'info, MXEAccount>>,
+    #[account(mut)]
+    /// CHECK: comp_def_account, checked by arcium program.
+    pub comp_def_account: UncheckedAccount<'info>,
+    pub arcium_program: Program<'info, Arcium>,
+    pub system_program: Program<'info, System>,
+}
+#[derive(Accounts)]
+pub struct PlaceBid<'info> {
+    #[account(mut)]
+    pub bidder: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [b"auction".as_ref(), auction.authority.as_ref(), &auction.auction_id.to_le_bytes()],
+        bump = auction.bump,
+    )]
+    pub auction: Account<'info, Auction>,
+    #[account(
+        mut,
+        seeds = [b"bid_queue".as_ref(), auction.key().as_ref()],
+        bump = bid_queue.bump,
+    )]
+    pub bid_queue: Box<Account<'info, BidQueue>>,
+    #[account(address = auction.quote_mint @ ErrorCode::InvalidMint)]
+    pub quote_mint: Box<InterfaceAccount<'info, Mint>>,
+    #[account(mut, address = auction.quote_vault @ ErrorCode::InvalidVault)]
+    pub quote_vault: Box<InterfaceAccount<'info, TokenAccount>>,
+    #[account(
+        mut,
+        token::mint = quote_mint,
+        token::authority = bidder,
+        token::token_program = quote_token_program,
+    )]
+    /// Omitted to deposit native SOL into a wrapped SOL auction.
+    pub bidder_quote_account: Option<Box<InterfaceAccount<'info, TokenAccount>>>,
+    #[account(
+        init_if_needed,
+        payer = bidder,
+        space = 8 + BidReceipt::INIT_SPACE,
+        seeds = [b"receipt".as_ref(), auction.key().as_ref(), bidder.key().as_ref()],
+        bump,
+    )]
+    pub receipt: Account<'info, BidReceipt>,
+    pub system_program: Program<'info, System>,
+    pub quote_token_program: Interface<'info, TokenInterface>,
+}
+
+#[queue_computation_accounts("process_bids", payer)]
+#[derive(Accounts)]
+#[instruction(computation_offset: u64)]
+pub struct ProcessBids<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    #[account(
+        seeds = [b"auction".as_ref(), auction.authority.as_ref(), &auction.auction_id.to_le_bytes()],
+        bump = auction.bump,
+    )]
+    pub auction: Account<'info, Auction>,
+    #[account(
+        seeds = [b"auction_state".as_ref(), auction.key().as_ref()],
+        bump = auction_state.bump,
+    )]
+    pub auction_state: Box<Account<'info, AuctionStateAccount>>,
+    #[account(
+        mut,
+        seeds = [b"bid_queue".as_ref(), auction.key().as_ref()],
+        bump = bid_queue.bump,
+    )]
+    pub bid_queue: Box<Account<'info, BidQueue>>,
+    #[account(
+        init_if_needed,
+        space = 9,
+        payer = payer,
+        seeds = [&SIGN_PDA_SEED],
+        bump,
+        address = derive_sign_pda!(),
+    )]
+    pub sign_pda_account: Account<'info, SignerAccount>,
+    #[account(address = derive_mxe_pda!())]
+    pub mxe_account: Account<'info, MXEAccount>,
+    #[account(mut, address = derive_mempool_pda!(mxe_account, ErrorCode::ClusterNotSet))]
+    /// CHECK: mempool_account, checked by the arcium program.
+    pub mempool_account: UncheckedAccount<'info>,
+    #[account(mut, address = derive_execpool_pda!(mxe_account, ErrorCode::ClusterNotSet))]
+    /// CHECK: executing_pool, checked by the arcium program.
+    pub executing_pool: UncheckedAccount<'info>,
+    #[account(mut, address = derive_comp_pda!(computation_offset, mxe_account, ErrorCode::ClusterNotSet))]
+    /// CHECK: computation_account, checked by the arcium program.
+    pub computation_account: UncheckedAccount<'info>,
+    #[account(address = derive_comp_def_pda!(COMP_DEF_OFFSET_PROCESS_BIDS))]
+    pub comp_def_account: Account<'info, ComputationDefinitionAccount>,
+    #[account(mut, address = derive_cluster_pda!(mxe_account, ErrorCode::ClusterNotSet))]
+    pub cluster_account: Account<'info, Cluster>,
+    #[account(mut, address = ARCIUM_FEE_POOL_ACCOUNT_ADDRESS)]
+    pub pool_account: Account<'info, FeePool>,
+    #[account(address = ARCIUM_CLOCK_ACCOUNT_ADDRESS)]
+    pub clock_account: Account<'info, ClockAccount>,
+    pub system_program: Program<'info, System>,
+    pub arcium_program: Program<'info, Arcium>,
+}
+
+#[callback_accounts("process_bids")]
+#[derive(Accounts)]
+pub struct ProcessBidsCallback<'info> {
+    pub arcium_program: Program<'info, Arcium>,
+    #[account(address = derive_comp_def_pda!(COMP_DEF_OFFSET_PROCESS_BIDS))]
+    pub comp_def_account: Account<'info, ComputationDefinitionAccount>,
+    #[account(address = derive_mxe_pda!())]
+    pub mxe_account: Account<'info, MXEAccount>,
+    /// CHECK: computation_account, checked by arcium program via constraints in the callback context.
+    pub computation_account: UncheckedAccount<'info>,
+    #[account(address = derive_cluster_pda!(mxe_account, ErrorCode::ClusterNotSet))]
+    pub cluster_account: Account<'info, Cluster>,
+    #[account(address = ::anchor_lang::solana_program::sysvar::instructions::ID)]
+    /// CHECK: instructions_sysvar, checked by the account constraint
+    pub instructions_sysvar: AccountInfo<'info>,
+    #[account(
+        mut,
+        seeds = [b"auction".as_ref(), auction.authority.as_ref(), &auction.auction_id.to_le_bytes()],
+        bump = auction.bump,
+    )]
+    pub auction: Account<'info, Auction>,
+    #[account(
+        mut,
+        seeds = [b"auction_state".as_ref(), auction.key().as_ref()],
+        bump = auction_state.bump,
+    )]
+    pub auction_state: Box<Account<'info, AuctionStateAccount>>,
+    #[account(
+        mut,
+        seeds = [b"bid_queue".as_ref(), auction.key().as_ref()],
+        bump = bid_queue.bump,
+    )]
+    pub bid_queue: Box<Account<'info, BidQueue>>,
+}
+#[queue_computation_accounts("process_bids", payer)]
+#[derive(Accounts)]
+#[instruction(computation_offset: u64)]
+pub struct RerankBids<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [b"auction".as_ref(), auction.authority.as_ref(), &auction.auction_id.to_le_bytes()],
+        bump = auction.bump,
+    )]
+    pub auction: Account<'info, Auction>,
+    #[account(
+        seeds = [b"auction_state".as_ref(), auction.key().as_ref()],
+        bump = auction_state.bump,
+    )]
+    pub auction_state: Box<Account<'info, AuctionStateAccount>>,
+    #[account(
+        mut,
+        seeds = [b"bid_queue".as_ref(), auction.key().as_ref()],
+        bump = bid_queue.bump,
+    )]
+    pub bid_queue: Box<Account<'info, BidQueue>>,
+    #[account(
+        init_if_needed,
+        space = 9,
+        payer = payer,
+        seeds = [&SIGN_PDA_SEED],
+        bump,
+        address = derive_sign_pda!(),
+    )]
+    pub sign_pda_account: Account<'info, SignerAccount>,
+    #[account(address = derive_mxe_pda!())]
+    pub mxe_account: Account<'info, MXEAccount>,
+    #[account(mut, address = derive_mempool_pda!(mxe_account, ErrorCode::ClusterNotSet))]
+    /// CHECK: mempool_account, checked by the arcium program.
+    pub mempool_account: UncheckedAccount<'info>,
+    #[account(mut, address = derive_execpool_pda!(mxe_account, ErrorCode::ClusterNotSet))]
+    /// CHECK: executing_pool, checked by the arcium program.
+    pub executing_pool: UncheckedAccount<'info>,
+    #[account(mut, address = derive_comp_pda!(computation_offset, mxe_account, ErrorCode::ClusterNotSet))]
+    /// CHECK: computation_account, checked by the arcium program.
+    pub computation_account: UncheckedAccount<'info>,
+    #[account(address = derive_comp_def_pda!(COMP_DEF_OFFSET_PROCESS_BIDS))]
+    pub comp_def_account: Account<'info, ComputationDefinitionAccount>,
+    #[account(mut, address = derive_cluster_pda!(mxe_account, ErrorCode::ClusterNotSet))]
+    pub cluster_account: Account<'info, Cluster>,
+    #[account(mut, address = ARCIUM_FEE_POOL_ACCOUNT_ADDRESS)]
+    pub pool_account: Account<'info, FeePool>,
+    #[account(address = ARCIUM_CLOCK_ACCOUNT_ADDRESS)]
+    pub clock_account: Account<'info, ClockAccount>,
+    pub system_program: Program<'info, System>,
+    pub arcium_program: Program<'info, Arcium>,
+}
+#[derive(Accounts)]
+pub struct ExpireBidBatch<'info> {
+    #[account(
+        seeds = [b"auction".as_ref(), auction.authority.as_ref(), &auction.auction_id.to_le_bytes()],
+        bump = auction.bump,
+    )]
+    pub auction: Account<'info, Auction>,
+    #[account(
+        mut,
+        seeds = [b"bid_queue".as_ref(), auction.key().as_ref()],
+        bump = bid_queue.bump,
+    )]
+    pub bid_queue: Box<Account<'info, BidQueue>>,
+}
+#[derive(Accounts)]
+pub struct ExpireCancelBid<'info> {
+    #[account(
+        mut,
+        seeds = [b"auction".as_ref(), auction.authority.as_ref(), &auction.auction_id.to_le_bytes()],
+        bump = auction.bump,
+    )]
+    pub auction: Account<'info, Auction>,
+    #[account(
+        mut,
+        seeds = [b"receipt".as_ref(), auction.key().as_ref(), receipt.bidder.as_ref()],
+        bump = receipt.bump,
+    )]
+    pub receipt: Account<'info, BidReceipt>,
+}
+#[init_computation_definition_accounts("second_winner", payer)]
+#[derive(Accounts)]
+pub struct InitSecondWinnerCompDef<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    #[account(mut, address = derive_mxe_pda!())]
+    pub mxe_account: Box<Account<'info, MXEAccount>>,
+    #[account(mut)]
+    /// CHECK: comp_def_account, checked by arcium program.
+    pub comp_def_account: UncheckedAccount<'info>,
+    pub arcium_program: Program<'info, Arcium>,
+    pub system_program: Program<'info, System>,
+}
+#[init_computation_definition_accounts("first_winner", payer)]
+#[derive(Accounts)]
+pub struct InitFirstWinnerCompDef<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    #[account(mut, address = derive_mxe_pda!())]
+    pub mxe_account: Box<Account<'info, MXEAccount>>,
+    #[account(mut)]
+    /// CHECK: comp_def_account, checked by arcium program.
+    pub comp_def_account: UncheckedAccount<'info>,
+    pub arcium_program: Program<'info, Arcium>,
+    pub system_program: Program<'info, System>,
+}
+#[queue_computation_accounts("first_winner", authority)]
+#[derive(Accounts)]
+#[instruction(computation_offset: u64)]
+pub struct FirstWinner<'info> {
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [b"auction".as_ref(), auction.authority.as_ref(), &auction.auction_id.to_le_bytes()],
+        bump = auction.bump,
+        has_one = authority @ ErrorCode::Unauthorized,
+    )]
+    pub auction: Account<'info, Auction>,
+    #[account(
+        seeds = [b"auction_state".as_ref(), auction.key().as_ref()],
+        bump = auction_state.bump,
+    )]
+    pub auction_state: Box<Account<'info, AuctionStateAccount>>,
+    #[account(
+        seeds = [b"allocations".as_ref(), auction.key().as_ref()],
+        bump = allocations.bump,
+    )]
+    pub allocations: Box<Account<'info, AuctionAllocations>>,
+    #[account(
+        init_if_needed,
+        space = 9,
+        payer = authority,
+        seeds = [&SIGN_PDA_SEED],
+        bump,
+        address = derive_sign_pda!(),
+    )]
+    pub sign_pda_account: Account<'info, SignerAccount>,
+    #[account(address = derive_mxe_pda!())]
+    pub mxe_account: Account<'info, MXEAccount>,
+    #[account(mut, address = derive_mempool_pda!(mxe_account, ErrorCode::ClusterNotSet))]
+    /// CHECK: mempool_account, checked by the arcium program.
+    pub mempool_account: UncheckedAccount<'info>,
+    #[account(mut, address = derive_execpool_pda!(mxe_account, ErrorCode::ClusterNotSet))]
+    /// CHECK: executing_pool, checked by the arcium program.
+    pub executing_pool: UncheckedAccount<'info>,
+    #[account(mut, address = derive_comp_pda!(computation_offset, mxe_account, ErrorCode::ClusterNotSet))]
+    /// CHECK: computation_account, checked by the arcium program.
+    pub computation_account: UncheckedAccount<'info>,
+    #[account(address = derive_comp_def_pda!(COMP_DEF_OFFSET_FIRST_WINNER))]
+    pub comp_def_account: Account<'info, ComputationDefinitionAccount>,
+    #[account(mut, address = derive_cluster_pda!(mxe_account, ErrorCode::ClusterNotSet))]
+    pub cluster_account: Account<'info, Cluster>,
+    #[account(mut, address = ARCIUM_FEE_POOL_ACCOUNT_ADDRESS)]
+    pub pool_account: Account<'info, FeePool>,
+    #[account(address = ARCIUM_CLOCK_ACCOUNT_ADDRESS)]
+    pub clock_account: Account<'info, ClockAccount>,
+    pub system_program: Program<'info, System>,
+    pub arcium_program: Program<'info, Arcium>,
+}
+
+#[callback_accounts("first_winner")]
+#[derive(Accounts)]
+pub struct FirstWinnerCallback<'info> {
+    pub arcium_program: Program<'info, Arcium>,
+    #[account(address = derive_comp_def_pda!(COMP_DEF_OFFSET_FIRST_WINNER))]
+    pub comp_def_account: Account<'info, ComputationDefinitionAccount>,
+    #[account(address = derive_mxe_pda!())]
+    pub mxe_account: Account<'info, MXEAccount>,
+    /// CHECK: computation_account, checked by arcium program via constraints in the callback context.
+    pub computation_account: UncheckedAccount<'info>,
+    #[account(address = derive_cluster_pda!(mxe_account, ErrorCode::ClusterNotSet))]
+    pub cluster_account: Account<'info, Cluster>,
+    #[account(address = ::anchor_lang::solana_program::sysvar::instructions::ID)]
+    /// CHECK: instructions_sysvar, checked by the account constraint
+    pub instructions_sysvar: AccountInfo<'info>,
+    #[account(
+        mut,
+        seeds = [b"auction".as_ref(), auction.authority.as_ref(), &auction.auction_id.to_le_bytes()],
+        bump = auction.bump,
+    )]
+    pub auction: Account<'info, Auction>,
+    #[account(
+        mut,
+        seeds = [b"allocations".as_ref(), auction.key().as_ref()],
+        bump = allocations.bump,
+    )]
+    pub allocations: Box<Account<'info, AuctionAllocations>>,
+}
+#[queue_computation_accounts("second_winner", authority)]
 #[derive(Accounts)]
 #[instruction(computation_offset: u64)]
-pub struct InitAuctionState<'info> {
+pub struct SecondWinner<'info> {
     #[account(mut)]
     pub authority: Signer<'info>,
-    #[account(init,payer=authority,space=8+Auction::INIT_SPACE,seeds=[b"auction".as_ref()],bump)]
+    #[account(
+        mut,
+        seeds = [b"auction".as_ref(), auction.authority.as_ref(), &auction.auction_id.to_le_bytes()],
+        bump = auction.bump,
+        has_one = authority @ ErrorCode::Unauthorized,
+    )]
     pub auction: Account<'info, Auction>,
+    #[account(
+        seeds = [b"auction_state".as_ref(), auction.key().as_ref()],
+        bump = auction_state.bump,
+    )]
+    pub auction_state: Box<Account<'info, AuctionStateAccount>>,
+    #[account(
+        seeds = [b"allocations".as_ref(), auction.key().as_ref()],
+        bump = allocations.bump,
+    )]
+    pub allocations: Box<Account<'info, AuctionAllocations>>,
     #[account(
         init_if_needed,
         space = 9,
@@ -432,7 +3092,7 @@ pub struct InitAuctionState<'info> {
     #[account(mut, address = derive_comp_pda!(computation_offset, mxe_account, ErrorCode::ClusterNotSet))]
     /// CHECK: computation_account, checked by the arcium program.
     pub computation_account: UncheckedAccount<'info>,
-    #[account(address = derive_comp_def_pda!(COMP_DEF_OFFSET_INIT_AUCTION_STATE))]
+    #[account(address = derive_comp_def_pda!(COMP_DEF_OFFSET_SECOND_WINNER))]
     pub comp_def_account: Account<'info, ComputationDefinitionAccount>,
     #[account(mut, address = derive_cluster_pda!(mxe_account, ErrorCode::ClusterNotSet))]
     pub cluster_account: Account<'info, Cluster>,
@@ -444,11 +3104,11 @@ pub struct InitAuctionState<'info> {
     pub arcium_program: Program<'info, Arcium>,
 }
 
-#[callback_accounts("init_auction_state")]
+#[callback_accounts("second_winner")]
 #[derive(Accounts)]
-pub struct InitAuctionStateCallback<'info> {
+pub struct SecondWinnerCallback<'info> {
     pub arcium_program: Program<'info, Arcium>,
-    #[account(address = derive_comp_def_pda!(COMP_DEF_OFFSET_INIT_AUCTION_STATE))]
+    #[account(address = derive_comp_def_pda!(COMP_DEF_OFFSET_SECOND_WINNER))]
     pub comp_def_account: Account<'info, ComputationDefinitionAccount>,
     #[account(address = derive_mxe_pda!())]
     pub mxe_account: Account<'info, MXEAccount>,
@@ -459,25 +3119,22 @@ pub struct InitAuctionStateCallback<'info> {
     #[account(address = ::anchor_lang::solana_program::sysvar::instructions::ID)]
     /// CHECK: instructions_sysvar, checked by the account constraint
     pub instructions_sysvar: AccountInfo<'info>,
-    #[account(mut)]
+    #[account(
+        mut,
+        seeds = [b"auction".as_ref(), auction.authority.as_ref(), &auction.auction_id.to_le_bytes()],
+        bump = auction.bump,
+    )]
     pub auction: Account<'info, Auction>,
+    #[account(
+        mut,
+        seeds = [b"allocations".as_ref(), auction.key().as_ref()],
+        bump = allocations.bump,
+    )]
+    pub allocations: Box<Account<'info, AuctionAllocations>>,
 }
-#[init_computation_definition_accounts("init_auction_state", payer)]
-#[derive(Accounts)]
-pub struct InitAuctionStateCompDef<'info> {
-    #[account(mut)]
-    pub payer: Signer<'info>,
-    #[account(mut, address = derive_mxe_pda!())]
-    pub mxe_account: Box<Account<'info, MXEAccount>>,
-    #[account(mut)]
-    /// CHECK: comp_def_account, checked by arcium program.
-    pub comp_def_account: UncheckedAccount<'info>,
-    pub arcium_program: Program<'info, Arcium>,
-    pub system_program: Program<'info, System>,
-}
-#[init_computation_definition_accounts("place_bid", payer)]
+#[init_computation_definition_accounts("uniform_price_winners", payer)]
 #[derive(Accounts)]
-pub struct InitPlaceBidCompDef<'info> {
+pub struct InitUniformPriceWinnersCompDef<'info> {
     #[account(mut)]
     pub payer: Signer<'info>,
     #[account(mut, address = derive_mxe_pda!())]
@@ -488,18 +3145,33 @@ pub struct InitPlaceBidCompDef<'info> {
     pub arcium_program: Program<'info, Arcium>,
     pub system_program: Program<'info, System>,
 }
-#[queue_computation_accounts("place_bid", bidder)]
+#[queue_computation_accounts("uniform_price_winners", authority)]
 #[derive(Accounts)]
 #[instruction(computation_offset: u64)]
-pub struct PlaceBid<'info> {
-    #[account(mut)]
-    pub bidder: Signer<'info>,
+pub struct UniformPriceWinners<'info> {
     #[account(mut)]
+    pub authority: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [b"auction".as_ref(), auction.authority.as_ref(), &auction.auction_id.to_le_bytes()],
+        bump = auction.bump,
+        has_one = authority @ ErrorCode::Unauthorized,
+    )]
     pub auction: Account<'info, Auction>,
+    #[account(
+        seeds = [b"auction_state".as_ref(), auction.key().as_ref()],
+        bump = auction_state.bump,
+    )]
+    pub auction_state: Box<Account<'info, AuctionStateAccount>>,
+    #[account(
+        seeds = [b"allocations".as_ref(), auction.key().as_ref()],
+        bump = allocations.bump,
+    )]
+    pub allocations: Box<Account<'info, AuctionAllocations>>,
     #[account(
         init_if_needed,
         space = 9,
-        payer = bidder,
+        payer = authority,
         seeds = [&SIGN_PDA_SEED],
         bump,
         address = derive_sign_pda!(),
@@ -516,7 +3188,7 @@ pub struct PlaceBid<'info> {
     #[account(mut, address = derive_comp_pda!(computation_offset, mxe_account, ErrorCode::ClusterNotSet))]
     /// CHECK: computation_account, checked by the arcium program.
     pub computation_account: UncheckedAccount<'info>,
-    #[account(address = derive_comp_def_pda!(COMP_DEF_OFFSET_PLACE_BID))]
+    #[account(address = derive_comp_def_pda!(COMP_DEF_OFFSET_UNIFORM_PRICE_WINNERS))]
     pub comp_def_account: Account<'info, ComputationDefinitionAccount>,
     #[account(mut, address = derive_cluster_pda!(mxe_account, ErrorCode::ClusterNotSet))]
     pub cluster_account: Account<'info, Cluster>,
@@ -528,11 +3200,11 @@ pub struct PlaceBid<'info> {
     pub arcium_program: Program<'info, Arcium>,
 }
 
-#[callback_accounts("place_bid")]
+#[callback_accounts("uniform_price_winners")]
 #[derive(Accounts)]
-pub struct PlaceBidCallback<'info> {
+pub struct UniformPriceWinnersCallback<'info> {
     pub arcium_program: Program<'info, Arcium>,
-    #[account(address = derive_comp_def_pda!(COMP_DEF_OFFSET_PLACE_BID))]
+    #[account(address = derive_comp_def_pda!(COMP_DEF_OFFSET_UNIFORM_PRICE_WINNERS))]
     pub comp_def_account: Account<'info, ComputationDefinitionAccount>,
     #[account(address = derive_mxe_pda!())]
     pub mxe_account: Account<'info, MXEAccount>,
@@ -543,12 +3215,22 @@ pub struct PlaceBidCallback<'info> {
     #[account(address = ::anchor_lang::solana_program::sysvar::instructions::ID)]
     /// CHECK: instructions_sysvar, checked by the account constraint
     pub instructions_sysvar: AccountInfo<'info>,
-    #[account(mut)]
+    #[account(
+        mut,
+        seeds = [b"auction".as_ref(), auction.authority.as_ref(), &auction.auction_id.to_le_bytes()],
+        bump = auction.bump,
+    )]
     pub auction: Account<'info, Auction>,
+    #[account(
+        mut,
+        seeds = [b"allocations".as_ref(), auction.key().as_ref()],
+        bump = allocations.bump,
+    )]
+    pub allocations: Box<Account<'info, AuctionAllocations>>,
 }
-#[init_computation_definition_accounts("second_winner", payer)]
+#[init_computation_definition_accounts("discriminatory_winners", payer)]
 #[derive(Accounts)]
-pub struct InitSecondWinnerCompDef<'info> {
+pub struct InitDiscriminatoryWinnersCompDef<'info> {
     #[account(mut)]
     pub payer: Signer<'info>,
     #[account(mut, address = derive_mxe_pda!())]
@@ -559,9 +3241,92 @@ pub struct InitSecondWinnerCompDef<'info> {
     pub arcium_program: Program<'info, Arcium>,
     pub system_program: Program<'info, System>,
 }
-#[init_computation_definition_accounts("first_winner", payer)]
+#[queue_computation_accounts("discriminatory_winners", authority)]
 #[derive(Accounts)]
-pub struct InitFirstWinnerCompDef<'info> {
+#[instruction(computation_offset: u64)]
+pub struct DiscriminatoryWinners<'info> {
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [b"auction".as_ref(), auction.authority.as_ref(), &auction.auction_id.to_le_bytes()],
+        bump = auction.bump,
+        has_one = authority @ ErrorCode::Unauthorized,
+    )]
+    pub auction: Account<'info, Auction>,
+    #[account(
+        seeds = [b"auction_state".as_ref(), auction.key().as_ref()],
+        bump = auction_state.bump,
+    )]
+    pub auction_state: Box<Account<'info, AuctionStateAccount>>,
+    #[account(
+        seeds = [b"allocations".as_ref(), auction.key().as_ref()],
+        bump = allocations.bump,
+    )]
+    pub allocations: Box<Account<'info, AuctionAllocations>>,
+    #[account(
+        init_if_needed,
+        space = 9,
+        payer = authority,
+        seeds = [&SIGN_PDA_SEED],
+        bump,
+        address = derive_sign_pda!(),
+    )]
+    pub sign_pda_account: Account<'info, SignerAccount>,
+    #[account(address = derive_mxe_pda!())]
+    pub mxe_account: Account<'info, MXEAccount>,
+    #[account(mut, address = derive_mempool_pda!(mxe_account, ErrorCode::ClusterNotSet))]
+    /// CHECK: mempool_account, checked by the arcium program.
+    pub mempool_account: UncheckedAccount<'info>,
+    #[account(mut, address = derive_execpool_pda!(mxe_account, ErrorCode::ClusterNotSet))]
+    /// CHECK: executing_pool, checked by the arcium program.
+    pub executing_pool: UncheckedAccount<'info>,
+    #[account(mut, address = derive_comp_pda!(computation_offset, mxe_account, ErrorCode::ClusterNotSet))]
+    /// CHECK: computation_account, checked by the arcium program.
+    pub computation_account: UncheckedAccount<'info>,
+    #[account(address = derive_comp_def_pda!(COMP_DEF_OFFSET_DISCRIMINATORY_WINNERS))]
+    pub comp_def_account: Account<'info, ComputationDefinitionAccount>,
+    #[account(mut, address = derive_cluster_pda!(mxe_account, ErrorCode::ClusterNotSet))]
+    pub cluster_account: Account<'info, Cluster>,
+    #[account(mut, address = ARCIUM_FEE_POOL_ACCOUNT_ADDRESS)]
+    pub pool_account: Account<'info, FeePool>,
+    #[account(address = ARCIUM_CLOCK_ACCOUNT_ADDRESS)]
+    pub clock_account: Account<'info, ClockAccount>,
+    pub system_program: Program<'info, System>,
+    pub arcium_program: Program<'info, Arcium>,
+}
+
+#[callback_accounts("discriminatory_winners")]
+#[derive(Accounts)]
+pub struct DiscriminatoryWinnersCallback<'info> {
+    pub arcium_program: Program<'info, Arcium>,
+    #[account(address = derive_comp_def_pda!(COMP_DEF_OFFSET_DISCRIMINATORY_WINNERS))]
+    pub comp_def_account: Account<'info, ComputationDefinitionAccount>,
+    #[account(address = derive_mxe_pda!())]
+    pub mxe_account: Account<'info, MXEAccount>,
+    /// CHECK: computation_account, checked by arcium program via constraints in the callback context.
+    pub computation_account: UncheckedAccount<'info>,
+    #[account(address = derive_cluster_pda!(mxe_account, ErrorCode::ClusterNotSet))]
+    pub cluster_account: Account<'info, Cluster>,
+    #[account(address = ::anchor_lang::solana_program::sysvar::instructions::ID)]
+    /// CHECK: instructions_sysvar, checked by the account constraint
+    pub instructions_sysvar: AccountInfo<'info>,
+    #[account(
+        mut,
+        seeds = [b"auction".as_ref(), auction.authority.as_ref(), &auction.auction_id.to_le_bytes()],
+        bump = auction.bump,
+    )]
+    pub auction: Account<'info, Auction>,
+    #[account(
+        mut,
+        seeds = [b"allocations".as_ref(), auction.key().as_ref()],
+        bump = allocations.bump,
+    )]
+    pub allocations: Box<Account<'info, AuctionAllocations>>,
+}
+#[init_computation_definition_accounts("bidder_result", payer)]
+#[derive(Accounts)]
+pub struct InitBidderResultCompDef<'info> {
     #[account(mut)]
     pub payer: Signer<'info>,
     #[account(mut, address = derive_mxe_pda!())]
@@ -572,18 +3337,37 @@ pub struct InitFirstWinnerCompDef<'info> {
     pub arcium_program: Program<'info, Arcium>,
     pub system_program: Program<'info, System>,
 }
-#[queue_computation_accounts("first_winner", authority)]
+#[queue_computation_accounts("bidder_result", bidder)]
 #[derive(Accounts)]
 #[instruction(computation_offset: u64)]
-pub struct FirstWinner<'info> {
+pub struct BidderResult<'info> {
     #[account(mut)]
-    pub authority: Signer<'info>,
-    #[account(mut, has_one = authority @ ErrorCode::Unauthorized)]
+    pub bidder: Signer<'info>,
+    #[account(
+        seeds = [b"auction".as_ref(), auction.authority.as_ref(), &auction.auction_id.to_le_bytes()],
+        bump = auction.bump,
+    )]
     pub auction: Account<'info, Auction>,
+    #[account(
+        seeds = [b"auction_state".as_ref(), auction.key().as_ref()],
+        bump = auction_state.bump,
+    )]
+    pub auction_state: Box<Account<'info, AuctionStateAccount>>,
+    #[account(
+        seeds = [b"allocations".as_ref(), auction.key().as_ref()],
+        bump = allocations.bump,
+    )]
+    pub allocations: Box<Account<'info, AuctionAllocations>>,
+    #[account(
+        seeds = [b"receipt".as_ref(), auction.key().as_ref(), bidder.key().as_ref()],
+        bump = receipt.bump,
+        has_one = bidder @ ErrorCode::Unauthorized,
+    )]
+    pub receipt: Account<'info, BidReceipt>,
     #[account(
         init_if_needed,
         space = 9,
-        payer = authority,
+        payer = bidder,
         seeds = [&SIGN_PDA_SEED],
         bump,
         address = derive_sign_pda!(),
@@ -600,7 +3384,7 @@ pub struct FirstWinner<'info> {
     #[account(mut, address = derive_comp_pda!(computation_offset, mxe_account, ErrorCode::ClusterNotSet))]
     /// CHECK: computation_account, checked by the arcium program.
     pub computation_account: UncheckedAccount<'info>,
-    #[account(address = derive_comp_def_pda!(COMP_DEF_OFFSET_FIRST_WINNER))]
+    #[account(address = derive_comp_def_pda!(COMP_DEF_OFFSET_BIDDER_RESULT))]
     pub comp_def_account: Account<'info, ComputationDefinitionAccount>,
     #[account(mut, address = derive_cluster_pda!(mxe_account, ErrorCode::ClusterNotSet))]
     pub cluster_account: Account<'info, Cluster>,
@@ -612,11 +3396,11 @@ pub struct FirstWinner<'info> {
     pub arcium_program: Program<'info, Arcium>,
 }
 
-#[callback_accounts("first_winner")]
+#[callback_accounts("bidder_result")]
 #[derive(Accounts)]
-pub struct FirstWinnerCallback<'info> {
+pub struct BidderResultCallback<'info> {
     pub arcium_program: Program<'info, Arcium>,
-    #[account(address = derive_comp_def_pda!(COMP_DEF_OFFSET_FIRST_WINNER))]
+    #[account(address = derive_comp_def_pda!(COMP_DEF_OFFSET_BIDDER_RESULT))]
     pub comp_def_account: Account<'info, ComputationDefinitionAccount>,
     #[account(address = derive_mxe_pda!())]
     pub mxe_account: Account<'info, MXEAccount>,
@@ -627,21 +3411,54 @@ pub struct FirstWinnerCallback<'info> {
     #[account(address = ::anchor_lang::solana_program::sysvar::instructions::ID)]
     /// CHECK: instructions_sysvar, checked by the account constraint
     pub instructions_sysvar: AccountInfo<'info>,
+    pub receipt: Account<'info, BidReceipt>,
+}
+#[init_computation_definition_accounts("cancel_bid", payer)]
+#[derive(Accounts)]
+pub struct InitCancelBidCompDef<'info> {
     #[account(mut)]
-    pub auction: Account<'info, Auction>,
+    pub payer: Signer<'info>,
+    #[account(mut, address = derive_mxe_pda!())]
+    pub mxe_account: Box<Account<'info, MXEAccount>>,
+    #[account(mut)]
+    /// CHECK: comp_def_account, checked by arcium program.
+    pub comp_def_account: UncheckedAccount<'info>,
+    pub arcium_program: Program<'info, Arcium>,
+    pub system_program: Program<'info, System>,
 }
-#[queue_computation_accounts("second_winner", authority)]
+#[queue_computation_accounts("cancel_bid", bidder)]
 #[derive(Accounts)]
 #[instruction(computation_offset: u64)]
-pub struct SecondWinner<'info> {
+pub struct CancelBid<'info> {
     #[account(mut)]
-    pub authority: Signer<'info>,
-    #[account(mut, has_one = authority @ ErrorCode::Unauthorized)]
+    pub bidder: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [b"auction".as_ref(), auction.authority.as_ref(), &auction.auction_id.to_le_bytes()],
+        bump = auction.bump,
+    )]
     pub auction: Account<'info, Auction>,
+    #[account(
+        seeds = [b"auction_state".as_ref(), auction.key().as_ref()],
+        bump = auction_state.bump,
+    )]
+    pub auction_state: Box<Account<'info, AuctionStateAccount>>,
+    #[account(
+        seeds = [b"bid_queue".as_ref(), auction.key().as_ref()],
+        bump = bid_queue.bump,
+    )]
+    pub bid_queue: Box<Account<'info, BidQueue>>,
+    #[account(
+        mut,
+        seeds = [b"receipt".as_ref(), auction.key().as_ref(), bidder.key().as_ref()],
+        bump = receipt.bump,
+        has_one = bidder @ ErrorCode::Unauthorized,
+    )]
+    pub receipt: Account<'info, BidReceipt>,
     #[account(
         init_if_needed,
         space = 9,
-        payer = authority,
+        payer = bidder,
         seeds = [&SIGN_PDA_SEED],
         bump,
         address = derive_sign_pda!(),
@@ -658,7 +3475,7 @@ pub struct SecondWinner<'info> {
     #[account(mut, address = derive_comp_pda!(computation_offset, mxe_account, ErrorCode::ClusterNotSet))]
     /// CHECK: computation_account, checked by the arcium program.
     pub computation_account: UncheckedAccount<'info>,
-    #[account(address = derive_comp_def_pda!(COMP_DEF_OFFSET_SECOND_WINNER))]
+    #[account(address = derive_comp_def_pda!(COMP_DEF_OFFSET_CANCEL_BID))]
     pub comp_def_account: Account<'info, ComputationDefinitionAccount>,
     #[account(mut, address = derive_cluster_pda!(mxe_account, ErrorCode::ClusterNotSet))]
     pub cluster_account: Account<'info, Cluster>,
@@ -670,11 +3487,11 @@ pub struct SecondWinner<'info> {
     pub arcium_program: Program<'info, Arcium>,
 }
 
-#[callback_accounts("second_winner")]
+#[callback_accounts("cancel_bid")]
 #[derive(Accounts)]
-pub struct SecondWinnerCallback<'info> {
+pub struct CancelBidCallback<'info> {
     pub arcium_program: Program<'info, Arcium>,
-    #[account(address = derive_comp_def_pda!(COMP_DEF_OFFSET_SECOND_WINNER))]
+    #[account(address = derive_comp_def_pda!(COMP_DEF_OFFSET_CANCEL_BID))]
     pub comp_def_account: Account<'info, ComputationDefinitionAccount>,
     #[account(address = derive_mxe_pda!())]
     pub mxe_account: Account<'info, MXEAccount>,
@@ -685,26 +3502,100 @@ pub struct SecondWinnerCallback<'info> {
     #[account(address = ::anchor_lang::solana_program::sysvar::instructions::ID)]
     /// CHECK: instructions_sysvar, checked by the account constraint
     pub instructions_sysvar: AccountInfo<'info>,
-    #[account(mut)]
+    #[account(
+        mut,
+        seeds = [b"auction".as_ref(), auction.authority.as_ref(), &auction.auction_id.to_le_bytes()],
+        bump = auction.bump,
+    )]
     pub auction: Account<'info, Auction>,
+    #[account(
+        mut,
+        seeds = [b"auction_state".as_ref(), auction.key().as_ref()],
+        bump = auction_state.bump,
+    )]
+    pub auction_state: Box<Account<'info, AuctionStateAccount>>,
+    #[account(
+        mut,
+        seeds = [b"receipt".as_ref(), auction.key().as_ref(), receipt.bidder.as_ref()],
+        bump = receipt.bump,
+    )]
+    pub receipt: Account<'info, BidReceipt>,
 }
 #[event]
 pub struct AuctionCreatedEvent {
     pub auction: Pubkey,
     pub authority: Pubkey,
+    pub auction_id: u64,
     pub auction_type: AuctionType,
     pub min_bid: u64,
     pub end_time: i64,
+    pub sale_mint: Pubkey,
+    pub quote_mint: Pubkey,
+    pub sale_amount: u64,
+    pub total_units: u64,
+    pub tie_break: TieBreak,
+    pub max_bids: u32,
+    pub allow_bid_revision: bool,
+    pub allowlist_root: Option<[u8; 32]>,
+    pub max_bid_spend: u64,
+}
+#[event]
+pub struct AllowlistRootUpdatedEvent {
+    pub auction: Pubkey,
+    pub allowlist_root: Option<[u8; 32]>,
 }
 #[event]
 pub struct BidPlacedEvent {
     pub auction: Pubkey,
-    pub bid_count: u8,
+    pub bid_count: u32,
+}
+#[event]
+pub struct BidsProcessedEvent {
+    pub auction: Pubkey,
+    pub processed: u8,
+    /// Bids still waiting in the queue.
+    pub queued: u32,
+}
+#[event]
+pub struct BidsRerankedEvent {
+    pub auction: Pubkey,
+    /// Active bids folded into the rebuilt ranking so far; the pass is complete
+    /// once this reaches `bid_count`.
+    pub reranked: u32,
+    pub bid_count: u32,
+}
+#[event]
+pub struct BidCancelledEvent {
+    pub auction: Pubkey,
+    pub bidder: Pubkey,
+    pub bid_count: u32,
 }
 #[event]
 pub struct AuctionClosedEvent {
     pub auction: Pubkey,
-    pub bid_count: u8,
+    pub bid_count: u32,
+}
+#[event]
+pub struct ComputationFailedEvent {
+    pub auction: Pubkey,
+    pub kind: ComputationKind,
+    pub computation_account: Pubkey,
+}
+/// A computation finished against an outdated auction state, or a cancellation
+/// landed after bidding closed, and was discarded. A stale cancellation can be
+/// submitted again while the auction is open.
+#[event]
+pub struct StaleComputationEvent {
+    pub auction: Pubkey,
+    /// Unset for a `process_bids` batch, whose bids stay queued.
+    pub bidder: Option<Pubkey>,
+    pub kind: ComputationKind,
+    pub computation_account: Pubkey,
+}
+#[event]
+pub struct AuctionCancelledEvent {
+    pub auction: Pubkey,
+    pub bid_count: u32,
 }
 #[event]
 pub struct AuctionResolvedEvent {
@@ -712,12 +3603,59 @@ pub struct AuctionResolvedEvent {
     pub winner: [u8; 32],
     pub payment_amount: u64,
     pub auction_type: AuctionType,
+    /// Another bid matched the winning price; the tie-break policy picked the winner.
+    pub price_shared: bool,
+}
+#[event]
+pub struct DepositWithdrawnEvent {
+    pub auction: Pubkey,
+    pub bidder: Pubkey,
+    pub amount: u64,
+}
+/// A bidder's `BidderResult`, readable only with their x25519 secret key.
+#[event]
+pub struct BidderResultEvent {
+    pub auction: Pubkey,
+    pub bidder: Pubkey,
+    pub encryption_key: [u8; 32],
+    pub nonce: [u8; 16],
+    pub ciphertexts: [[u8; 32]; 5],
+}
+#[event]
+pub struct BidReceiptClosedEvent {
+    pub auction: Pubkey,
+    pub bidder: Pubkey,
+}
+#[event]
+pub struct UniformPriceResolvedEvent {
+    pub auction: Pubkey,
+    pub clearing_price: u64,
+    pub units_sold: u64,
+    pub price_shared: bool,
+}
+#[event]
+pub struct DiscriminatoryResolvedEvent {
+    pub auction: Pubkey,
+    pub units_sold: u64,
+    pub total_payment: u64,
+    pub price_shared: bool,
+}
+#[event]
+pub struct AllocationClaimedEvent {
+    pub auction: Pubkey,
+    pub bidder: Pubkey,
+    pub quantity: u64,
+    pub amount: u64,
+}
+#[event]
+pub struct ProceedsClaimedEvent {
+    pub auction: Pubkey,
+    pub payment_amount: u64,
+    pub unsold_amount: u64,
 }
 
 #[error_code]
 pub enum ErrorCode {
-    #[msg("The computation was aborted")]
-    AbortedComputation,
     #[msg("Cluster not set")]
     ClusterNotSet,
     #[msg("Auction is not open for bidding")]
@@ -728,4 +3666,186 @@ pub enum ErrorCode {
     WrongAuctionType,
     #[msg("Unauthorized")]
     Unauthorized,
-}
\ No newline at end of file
+    #[msg("Auction end time has passed, no more bids are accepted")]
+    BiddingEnded,
+    #[msg("Auction end time has not been reached yet")]
+    AuctionNotEnded,
+    #[msg("Auction end time must be in the future")]
+    InvalidEndTime,
+    #[msg("Deposit must cover at least the minimum bid")]
+    DepositTooLow,
+    #[msg("Auction has not been resolved yet")]
+    AuctionNotResolved,
+    #[msg("Arithmetic overflow")]
+    MathOverflow,
+    #[msg("Sale amount must be greater than zero")]
+    InvalidSaleAmount,
+    #[msg("Mint does not match the auction")]
+    InvalidMint,
+    #[msg("Token vault does not match the auction")]
+    InvalidVault,
+    #[msg("Already claimed")]
+    AlreadyClaimed,
+    #[msg("Total units must be 1 for single-lot auctions, and at least 1 and no more than the sale amount otherwise")]
+    InvalidTotalUnits,
+    #[msg("No allocation at this index")]
+    NoAllocation,
+    #[msg("Pro-rata tie-breaking needs a multi-unit auction")]
+    InvalidTieBreak,
+    #[msg("Max bids must be greater than zero")]
+    InvalidMaxBids,
+    #[msg("Auction has reached its maximum number of bids")]
+    MaxBidsReached,
+    #[msg("Deposit must be withdrawn before the receipt is closed")]
+    DepositNotWithdrawn,
+    #[msg("Signer already has a bid in this auction")]
+    DuplicateBid,
+    #[msg("Signer is not on the auction allowlist")]
+    NotAllowlisted,
+    #[msg("Auction has not been resolved or cancelled yet")]
+    AuctionNotFinalized,
+    #[msg("Auction has already been resolved or cancelled")]
+    AuctionAlreadyFinalized,
+    #[msg("Another computation for this auction is pending or waiting for a retry")]
+    ComputationPending,
+    #[msg("No failed computation to retry")]
+    NothingToRetry,
+    #[msg("The bid queue is full; process queued bids first")]
+    BidQueueFull,
+    #[msg("No bids are waiting in the queue")]
+    BidQueueEmpty,
+    #[msg("No bid batch is being processed")]
+    NoBatchInFlight,
+    #[msg("Queued bids or cancellations have not reached the auction state yet")]
+    BidsInFlight,
+    #[msg("Signer has no active bid to cancel")]
+    NoActiveBid,
+    #[msg("Receipt accounts must match the queued bids in the batch, in order")]
+    InvalidBidReceipts,
+    #[msg("The computation has not timed out yet")]
+    ComputationNotExpired,
+    #[msg("The signer's previous bid is still waiting in the queue")]
+    BidAlreadyQueued,
+    #[msg("The ranking must be rebuilt with rerank_bids before resolution")]
+    RerankRequired,
+    #[msg("The ranking does not need to be rebuilt")]
+    NothingToRerank,
+    #[msg("Native SOL deposits need a wrapped SOL quote mint")]
+    NativeDepositNotSupported,
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn leaf(bidder: &Pubkey, max_allocation: u64) -> [u8; 32] {
+        hashv(&[bidder.as_ref(), &max_allocation.to_le_bytes()]).to_bytes()
+    }
+
+    fn parent(a: [u8; 32], b: [u8; 32]) -> [u8; 32] {
+        if a <= b {
+            hashv(&[a.as_ref(), b.as_ref()]).to_bytes()
+        } else {
+            hashv(&[b.as_ref(), a.as_ref()]).to_bytes()
+        }
+    }
+
+    fn allocations(slots: &[(Pubkey, u64, u64)]) -> AuctionAllocations {
+        let mut allocations = AuctionAllocations {
+            bump: 0,
+            auction: Pubkey::default(),
+            clearing_price: 0,
+            allocations: [Allocation::default(); MAX_WINNERS],
+            winner: Pubkey::default(),
+            resolved_at: 0,
+        };
+        for (i, &(bidder, quantity, payment)) in slots.iter().enumerate() {
+            allocations.allocations[i] = Allocation {
+                bidder,
+                quantity,
+                payment,
+                claimed: false,
+            };
+        }
+        allocations
+    }
+
+    #[test]
+    fn allowlist_accepts_members_and_rejects_others() {
+        let (a, b, c) = (Pubkey::new_unique(), Pubkey::new_unique(), Pubkey::new_unique());
+        let (leaf_a, leaf_b, leaf_c) = (leaf(&a, 10), leaf(&b, 20), leaf(&c, 0));
+        let root = parent(parent(leaf_a, leaf_b), leaf_c);
+
+        assert!(allowlist_contains(&root, &a, 10, &[leaf_b, leaf_c]));
+        assert!(allowlist_contains(&root, &b, 20, &[leaf_a, leaf_c]));
+        assert!(allowlist_contains(&root, &c, 0, &[parent(leaf_a, leaf_b)]));
+        // The cap is part of the leaf, so a bidder cannot claim a larger one.
+        assert!(!allowlist_contains(&root, &a, 11, &[leaf_b, leaf_c]));
+        assert!(!allowlist_contains(&root, &Pubkey::new_unique(), 10, &[leaf_b, leaf_c]));
+        assert!(!allowlist_contains(&root, &a, 10, &[]));
+    }
+
+    #[test]
+    fn pubkey_halves_round_trip() {
+        let key = Pubkey::new_unique();
+        let (lo, hi) = pubkey_halves(&key);
+        assert_eq!(pubkey_from_halves(lo, hi), key);
+        assert_eq!(pubkey_halves(&Pubkey::default()), (0, 0));
+    }
+
+    #[test]
+    fn mark_resolved_picks_first_filled_slot() {
+        let (a, b) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let mut resolved = allocations(&[(a, 0, 0), (b, 2, 80)]);
+        resolved.mark_resolved(42);
+        assert_eq!(resolved.winner, b);
+        assert_eq!(resolved.resolved_at, 42);
+
+        let mut unsold = allocations(&[]);
+        unsold.mark_resolved(42);
+        assert_eq!(unsold.winner, Pubkey::default());
+    }
+
+    #[test]
+    fn payments_sum_over_every_slot_a_bidder_holds() {
+        let (a, b) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let resolved = allocations(&[(a, 2, 100), (b, 1, 40), (a, 1, 45)]);
+        assert_eq!(resolved.units_sold(), 4);
+        assert_eq!(resolved.total_payment().unwrap(), 185);
+        assert_eq!(resolved.allocation_of(&a).unwrap(), (3, 145));
+        assert_eq!(resolved.payment_owed_by(&b).unwrap(), 40);
+        assert_eq!(resolved.payment_owed_by(&Pubkey::new_unique()).unwrap(), 0);
+    }
+
+    #[test]
+    fn state_write_bumps_version() {
+        let mut state = AuctionStateAccount {
+            bump: 0,
+            auction: Pubkey::default(),
+            state_nonce: 0,
+            state_version: 0,
+            encrypted_state: vec![],
+        };
+        state.write(&[[1u8; 32]; ENCRYPTED_STATE_FIELDS], 7);
+        state.write(&[[2u8; 32]; ENCRYPTED_STATE_FIELDS], 8);
+        assert_eq!(state.state_version, 2);
+        assert_eq!(state.state_nonce, 8);
+        assert_eq!(state.encrypted_state.len(), ENCRYPTED_STATE_FIELDS);
+    }
+
+    #[test]
+    fn bid_queue_tracks_processed_sequence() {
+        let queue = BidQueue {
+            bump: 0,
+            auction: Pubkey::default(),
+            next_seq: 5,
+            processed_seq: 3,
+            in_flight: None,
+            rerank_from: 0,
+            reranked: 0,
+            entries: vec![],
+        };
+        assert!(queue.is_processed(2));
+        assert!(!queue.is_processed(3));
+        assert!(!queue.is_processed(4));
+    }
+}
@@ -2,7 +2,7 @@ import * as anchor from "@coral-xyz/anchor";
 import { Program } from "@coral-xyz/anchor";
 import { PublicKey } from "@solana/web3.js";
 import { WsConfidentialPrimaryMarketAuction } from "../target/types/ws_confidential_primary_market_auction";
-import { randomBytes } from "crypto";
+import { createHash, randomBytes } from "crypto";
 import {
   awaitComputationFinalization,
   getArciumEnv,
@@ -22,6 +22,13 @@ import {
   getClusterAccAddress,
   x25519,
 } from "@arcium-hq/client";
+import {
+  NATIVE_MINT,
+  TOKEN_PROGRAM_ID,
+  createMint,
+  getOrCreateAssociatedTokenAccount,
+  mintTo,
+} from "@solana/spl-token";
 import * as fs from "fs";
 import * as os from "os";
 import { expect } from "chai";
@@ -31,6 +38,9 @@ import { expect } from "chai";
 // For devnet/testnet: specific cluster offset
 const CLUSTER_OFFSET: number | null = null;
 
+// Mirrors BID_BATCH_SIZE in the program.
+const BID_BATCH_SIZE = 4;
+
 /**
  * Gets the cluster account address based on configuration.
  * - If CLUSTER_OFFSET is set: Uses getClusterAccAddress (devnet/testnet)
@@ -68,6 +78,10 @@ describe("WsConfidentialPrimaryMarketAuction", () => {
 let owner: anchor.web3.Keypair;
 let mxePublicKey: Uint8Array;
 let compDefsInitialized = false;
+let saleMint: PublicKey;
+let quoteMint: PublicKey;
+let ownerSaleAccount: PublicKey;
+let ownerQuoteAccount: PublicKey;
 before(async () => {
     owner = readKpJson(`/Users/air/.config/solana/local.json`);
 
@@ -86,8 +100,8 @@ before(async () => {
       await initCompDef(program, owner, "init_auction_state");
       console.log("   Done.");
 
-      console.log("2. Initializing place_bid comp def...");
-      await initCompDef(program, owner, "place_bid");
+      console.log("2. Initializing process_bids comp def...");
+      await initCompDef(program, owner, "process_bids");
       console.log("   Done.");
 
       console.log("3. Initializing first_winner comp def...");
@@ -96,17 +110,408 @@ before(async () => {
 
       console.log("4. Initializing second_winner comp def...");
       await initCompDef(program, owner, "second_winner");
+      console.log("   Done.");
+
+      console.log("5. Initializing uniform_price_winners comp def...");
+      await initCompDef(program, owner, "uniform_price_winners");
+      console.log("   Done.");
+
+      console.log("6. Initializing discriminatory_winners comp def...");
+      await initCompDef(program, owner, "discriminatory_winners");
+      console.log("   Done.");
+
+      console.log("7. Initializing bidder_result comp def...");
+      await initCompDef(program, owner, "bidder_result");
+      console.log("   Done.");
+
+      console.log("8. Initializing cancel_bid comp def...");
+      await initCompDef(program, owner, "cancel_bid");
       console.log("   Done.\n");
 
       compDefsInitialized = true;
     }
 
+    // The issued asset and the quote token (e.g. USDC) the sale is priced in.
+    saleMint = await createMint(
+      provider.connection,
+      owner,
+      owner.publicKey,
+      null,
+      6
+    );
+    quoteMint = await createMint(
+      provider.connection,
+      owner,
+      owner.publicKey,
+      null,
+      6
+    );
+    ownerSaleAccount = (
+      await getOrCreateAssociatedTokenAccount(
+        provider.connection,
+        owner,
+        saleMint,
+        owner.publicKey
+      )
+    ).address;
+    ownerQuoteAccount = (
+      await getOrCreateAssociatedTokenAccount(
+        provider.connection,
+        owner,
+        quoteMint,
+        owner.publicKey
+      )
+    ).address;
+    await mintTo(
+      provider.connection,
+      owner,
+      saleMint,
+      ownerSaleAccount,
+      owner,
+      1_000_000
+    );
+    await mintTo(
+      provider.connection,
+      owner,
+      quoteMint,
+      ownerQuoteAccount,
+      owner,
+      1_000_000
+    );
+
   });
+
+  // Shared helpers for the scenario tests below.
+  type Bidder = {
+    keypair: anchor.web3.Keypair;
+    quoteAccount: PublicKey;
+    saleAccount: PublicKey;
+    encryptionPubkey: Uint8Array;
+    cipher: RescueCipher;
+  };
+
+  type AuctionParams = {
+    auctionType: "firstPrice" | "secondPrice" | "uniformPrice" | "discriminatory";
+    minBid?: number;
+    saleAmount?: number;
+    totalUnits?: number;
+    tieBreak?: "earliestBid" | "proRata" | "random";
+    maxBids?: number;
+    allowBidRevision?: boolean;
+    allowlistRoot?: number[] | null;
+    maxBidSpend?: number;
+    quoteMint?: PublicKey;
+  };
+
+  type BidParams = {
+    amount: number;
+    quantity: number;
+    maxSpend?: number;
+    deposit: number;
+    maxAllocation?: number;
+    proof?: number[][];
+    // Deposit native SOL instead of quote tokens.
+    native?: boolean;
+  };
+
+  async function newBidder(quoteAmount = 1_000_000): Promise<Bidder> {
+    const keypair = anchor.web3.Keypair.generate();
+    const airdropSig = await provider.connection.requestAirdrop(
+      keypair.publicKey,
+      2 * anchor.web3.LAMPORTS_PER_SOL
+    );
+    await provider.connection.confirmTransaction(airdropSig, "confirmed");
+    const quoteAccount = (
+      await getOrCreateAssociatedTokenAccount(
+        provider.connection,
+        owner,
+        quoteMint,
+        keypair.publicKey
+      )
+    ).address;
+    const saleAccount = (
+      await getOrCreateAssociatedTokenAccount(
+        provider.connection,
+        owner,
+        saleMint,
+        keypair.publicKey
+      )
+    ).address;
+    await mintTo(
+      provider.connection,
+      owner,
+      quoteMint,
+      quoteAccount,
+      owner,
+      quoteAmount
+    );
+    const privateKey = x25519.utils.randomSecretKey();
+    const encryptionPubkey = x25519.getPublicKey(privateKey);
+    const cipher = new RescueCipher(
+      x25519.getSharedSecret(privateKey, mxePublicKey)
+    );
+    return { keypair, quoteAccount, saleAccount, encryptionPubkey, cipher };
+  }
+
+  function arciumAccounts(computationOffset: anchor.BN, circuitName: string) {
+    return {
+      computationAccount: getComputationAccAddress(
+        arciumEnv.arciumClusterOffset,
+        computationOffset
+      ),
+      clusterAccount,
+      mxeAccount: getMXEAccAddress(program.programId),
+      mempoolAccount: getMempoolAccAddress(arciumEnv.arciumClusterOffset),
+      executingPool: getExecutingPoolAccAddress(arciumEnv.arciumClusterOffset),
+      compDefAccount: getCompDefAccAddress(
+        program.programId,
+        Buffer.from(getCompDefAccOffset(circuitName)).readUInt32LE()
+      ),
+    };
+  }
+
+  function auctionPda(seed: string, auction: PublicKey, bidder?: PublicKey) {
+    const seeds = [Buffer.from(seed), auction.toBuffer()];
+    if (bidder) {
+      seeds.push(bidder.toBuffer());
+    }
+    return PublicKey.findProgramAddressSync(seeds, program.programId)[0];
+  }
+
+  function finalize(computationOffset: anchor.BN) {
+    return awaitComputationFinalization(
+      provider as anchor.AnchorProvider,
+      computationOffset,
+      program.programId,
+      "confirmed"
+    );
+  }
+
+  async function expectError(action: Promise<unknown>, code: string) {
+    let error: unknown;
+    try {
+      await action;
+    } catch (err) {
+      error = err;
+    }
+    expect(error, `expected ${code}`).to.not.equal(undefined);
+    expect(String(error)).to.include(code);
+  }
+
+  async function createAuction(params: AuctionParams): Promise<PublicKey> {
+    const auctionId = new anchor.BN(randomBytes(8), "hex");
+    const [auction] = PublicKey.findProgramAddressSync(
+      [
+        Buffer.from("auction"),
+        owner.publicKey.toBuffer(),
+        auctionId.toArrayLike(Buffer, "le", 8),
+      ],
+      program.programId
+    );
+    const computationOffset = new anchor.BN(randomBytes(8), "hex");
+    const nonce = randomBytes(16);
+    const createdPromise = awaitEvent("auctionCreatedEvent");
+    await program.methods
+      .initAuctionState(
+        computationOffset,
+        auctionId,
+        { [params.auctionType]: {} } as any,
+        new anchor.BN(params.minBid ?? 10),
+        new anchor.BN(Date.now() / 1000 + 3600),
+        new anchor.BN(params.saleAmount ?? 1_000),
+        new anchor.BN(params.totalUnits ?? 1),
+        { [params.tieBreak ?? "earliestBid"]: {} } as any,
+        params.maxBids ?? 16,
+        params.allowBidRevision ?? false,
+        params.allowlistRoot ?? null,
+        new anchor.BN(params.maxBidSpend ?? 0),
+        new anchor.BN(deserializeLE(nonce).toString())
+      )
+      .accountsPartial({
+        authority: owner.publicKey,
+        auction,
+        saleMint,
+        quoteMint: params.quoteMint ?? quoteMint,
+        authoritySaleAccount: ownerSaleAccount,
+        saleTokenProgram: TOKEN_PROGRAM_ID,
+        quoteTokenProgram: TOKEN_PROGRAM_ID,
+        ...arciumAccounts(computationOffset, "init_auction_state"),
+      })
+      .rpc({ skipPreflight: true, commitment: "confirmed" });
+    await finalize(computationOffset);
+    await createdPromise;
+    return auction;
+  }
+
+  async function placeBid(auction: PublicKey, bidder: Bidder, bid: BidParams) {
+    const { quoteMint } = await program.account.auction.fetch(auction);
+    const nonce = randomBytes(16);
+    const ciphertext = bidder.cipher.encrypt(
+      [BigInt(bid.amount), BigInt(bid.quantity), BigInt(bid.maxSpend ?? 0)],
+      nonce
+    );
+    return program.methods
+      .placeBid(
+        Array.from(ciphertext[0]),
+        Array.from(ciphertext[1]),
+        Array.from(ciphertext[2]),
+        Array.from(bidder.encryptionPubkey),
+        new anchor.BN(deserializeLE(nonce).toString()),
+        new anchor.BN(bid.deposit),
+        new anchor.BN(bid.maxAllocation ?? 0),
+        bid.proof ?? []
+      )
+      .accountsPartial({
+        bidder: bidder.keypair.publicKey,
+        auction,
+        quoteMint,
+        bidderQuoteAccount: bid.native ? null : bidder.quoteAccount,
+        quoteTokenProgram: TOKEN_PROGRAM_ID,
+      })
+      .signers([bidder.keypair])
+      .rpc({ commitment: "confirmed" });
+  }
+
+  // process_bids takes the receipts of the next batch, in queue order.
+  async function batchReceipts(auction: PublicKey) {
+    const bidQueue = await program.account.bidQueue.fetch(
+      auctionPda("bid_queue", auction)
+    );
+    return bidQueue.entries.slice(0, BID_BATCH_SIZE).map((entry) => ({
+      pubkey: auctionPda("receipt", auction, entry.bidder),
+      isSigner: false,
+      isWritable: true,
+    }));
+  }
+
+  async function processBids(auction: PublicKey) {
+    const computationOffset = new anchor.BN(randomBytes(8), "hex");
+    const processedPromise = awaitEvent("bidsProcessedEvent");
+    await program.methods
+      .processBids(computationOffset)
+      .accountsPartial({
+        payer: owner.publicKey,
+        auction,
+        ...arciumAccounts(computationOffset, "process_bids"),
+      })
+      .remainingAccounts(await batchReceipts(auction))
+      .rpc({ skipPreflight: true, commitment: "confirmed" });
+    await finalize(computationOffset);
+    return processedPromise;
+  }
+
+  async function drainBidQueue(auction: PublicKey) {
+    let queued = (
+      await program.account.bidQueue.fetch(auctionPda("bid_queue", auction))
+    ).entries.length;
+    while (queued > 0) {
+      queued = (await processBids(auction)).queued;
+    }
+  }
+
+  // rerank_bids takes the receipts of the active bids in arrival order,
+  // picking up where the previous batch left off.
+  async function rerankBids(auction: PublicKey) {
+    const receipts = (
+      await program.account.bidReceipt.all([
+        { memcmp: { offset: 9, bytes: auction.toBase58() } },
+      ])
+    )
+      .filter((x) => x.account.active)
+      .sort((a, b) => a.account.queueSeq.cmp(b.account.queueSeq));
+    while ((await program.account.auction.fetch(auction)).rerankRequired) {
+      const { rerankFrom } = await program.account.bidQueue.fetch(
+        auctionPda("bid_queue", auction)
+      );
+      const batch = receipts
+        .filter((x) => x.account.queueSeq.gte(rerankFrom))
+        .slice(0, BID_BATCH_SIZE);
+      const computationOffset = new anchor.BN(randomBytes(8), "hex");
+      const rerankedPromise = awaitEvent("bidsRerankedEvent");
+      await program.methods
+        .rerankBids(computationOffset)
+        .accountsPartial({
+          payer: owner.publicKey,
+          auction,
+          ...arciumAccounts(computationOffset, "process_bids"),
+        })
+        .remainingAccounts(
+          batch.map((x) => ({ pubkey: x.publicKey, isSigner: false, isWritable: false }))
+        )
+        .rpc({ skipPreflight: true, commitment: "confirmed" });
+      await finalize(computationOffset);
+      await rerankedPromise;
+    }
+  }
+
+  function closeAuction(auction: PublicKey) {
+    return program.methods
+      .closeAuction()
+      .accountsPartial({ authority: owner.publicKey, auction })
+      .rpc({ commitment: "confirmed" });
+  }
+
+  async function cancelBid(auction: PublicKey, bidder: Bidder) {
+    const computationOffset = new anchor.BN(randomBytes(8), "hex");
+    await program.methods
+      .cancelBid(computationOffset)
+      .accountsPartial({
+        bidder: bidder.keypair.publicKey,
+        auction,
+        ...arciumAccounts(computationOffset, "cancel_bid"),
+      })
+      .signers([bidder.keypair])
+      .rpc({ skipPreflight: true, commitment: "confirmed" });
+    return finalize(computationOffset);
+  }
+
+  function queueResolution(
+    auction: PublicKey,
+    auctionType: AuctionParams["auctionType"],
+    computationOffset: anchor.BN,
+    options: { skipPreflight?: boolean } = {}
+  ) {
+    const circuitName = {
+      firstPrice: "first_winner",
+      secondPrice: "second_winner",
+      uniformPrice: "uniform_price_winners",
+      discriminatory: "discriminatory_winners",
+    }[auctionType];
+    const accounts = {
+      authority: owner.publicKey,
+      auction,
+      ...arciumAccounts(computationOffset, circuitName),
+    };
+    const method = {
+      firstPrice: () => program.methods.firstWinner(computationOffset),
+      secondPrice: () => program.methods.secondWinner(computationOffset),
+      uniformPrice: () => program.methods.uniformPriceWinners(computationOffset),
+      discriminatory: () =>
+        program.methods.discriminatoryWinners(computationOffset),
+    }[auctionType]();
+    return method
+      .accountsPartial(accounts)
+      .rpc({ commitment: "confirmed", ...options });
+  }
+
+  async function resolveAuction(
+    auction: PublicKey,
+    auctionType: AuctionParams["auctionType"]
+  ) {
+    const computationOffset = new anchor.BN(randomBytes(8), "hex");
+    await queueResolution(auction, auctionType, computationOffset, {
+      skipPreflight: true,
+    });
+    await finalize(computationOffset);
+    return program.account.auctionAllocations.fetch(
+      auctionPda("allocations", auction)
+    );
+  }
+
   describe("First Price Auction", () => {
     it("creates an auction, accepts bids, and determines winner (pays their bid)", async () => {
       const bidder = owner;
       const bidderPubkey = bidder.publicKey.toBytes();
-      const { lo: bidderLo, hi: bidderHi } = splitPubkeyToU128s(bidderPubkey);
       const privateKey = x25519.utils.randomSecretKey();
       const publicKey = x25519.getPublicKey(privateKey);
       const sharedSecret = x25519.getSharedSecret(privateKey, mxePublicKey);
@@ -114,22 +519,40 @@ before(async () => {
       const auctionCreatedPromise = awaitEvent("auctionCreatedEvent");
       const createComputationOffset = new anchor.BN(randomBytes(8), "hex");
 
+      const auctionId = new anchor.BN(randomBytes(8), "hex");
       const [auctionPDA] = PublicKey.findProgramAddressSync(
-        [Buffer.from("auction"), owner.publicKey.toBuffer()],
+        [
+          Buffer.from("auction"),
+          owner.publicKey.toBuffer(),
+          auctionId.toArrayLike(Buffer, "le", 8),
+        ],
         program.programId
       );
       const createNonce = randomBytes(16);
       const createSig = await program.methods
         .initAuctionState(
           createComputationOffset,
+          auctionId,
           { firstPrice: {} }, // AuctionType::FirstPrice
-          new anchor.BN(100), // min_bid: 100 lamports
+          new anchor.BN(100), // min_bid: 100 quote token base units
           new anchor.BN(Date.now() / 1000 + 3600), // end_time: 1 hour from now
+          new anchor.BN(1_000), // sale_amount: units of the sale mint on offer
+          new anchor.BN(1), // total_units: single-lot auctions sell one unit
+          { earliestBid: {} }, // TieBreak::EarliestBid
+          16, // max_bids
+          false, // allow_bid_revision: a second bid from the same wallet is rejected
+          null, // allowlist_root: anyone may bid
+          new anchor.BN(0), // max_bid_spend: no per-bid cap
           new anchor.BN(deserializeLE(createNonce).toString()) // nonce for MXE
         )
         .accountsPartial({
           authority: owner.publicKey,
           auction: auctionPDA,
+          saleMint,
+          quoteMint,
+          authoritySaleAccount: ownerSaleAccount,
+          saleTokenProgram: TOKEN_PROGRAM_ID,
+          quoteTokenProgram: TOKEN_PROGRAM_ID,
           computationAccount: getComputationAccAddress(
             arciumEnv.arciumClusterOffset,
             createComputationOffset
@@ -163,26 +586,49 @@ before(async () => {
         );
         expect(auctionCreatedEvent.minBid.toNumber()).to.equal(100);
         const bidPlacedPromise = awaitEvent("bidPlacedEvent");
-      const bidComputationOffset = new anchor.BN(randomBytes(8), "hex");
       const bidAmount = BigInt(500);
       const nonce = randomBytes(16);
-      const bidPlaintext = [bidderLo, bidderHi, bidAmount];
+      const bidQuantity = BigInt(1);
+      const bidMaxSpend = BigInt(0); // no spend cap
+      // The bidder identity is not encrypted: the program binds the bid to the signer.
+      const bidPlaintext = [bidAmount, bidQuantity, bidMaxSpend];
       const bidCiphertext = cipher.encrypt(bidPlaintext, nonce);
       const placeBidSig = await program.methods
         .placeBid(
-          bidComputationOffset,
-          Array.from(bidCiphertext[0]), // encrypted_bidder_lo
-          Array.from(bidCiphertext[1]), // encrypted_bidder_hi
-          Array.from(bidCiphertext[2]), // encrypted_amount
+          Array.from(bidCiphertext[0]), // encrypted_amount
+          Array.from(bidCiphertext[1]), // encrypted_quantity
+          Array.from(bidCiphertext[2]), // encrypted_max_spend
           Array.from(publicKey),
-          new anchor.BN(deserializeLE(nonce).toString())
+          new anchor.BN(deserializeLE(nonce).toString()),
+          new anchor.BN(1_000), // deposit: upper bound of the bid, in quote tokens
+          new anchor.BN(0), // max_allocation: only checked with an allowlist
+          [] // allowlist_proof
         )
         .accountsPartial({
           bidder: bidder.publicKey,
           auction: auctionPDA,
+          quoteMint,
+          bidderQuoteAccount: ownerQuoteAccount,
+          quoteTokenProgram: TOKEN_PROGRAM_ID,
+        })
+        .rpc({ commitment: "confirmed" });
+
+      console.log("   Place bid tx:", placeBidSig);
+      const bidPlacedEvent = await bidPlacedPromise;
+      console.log("   Bid queued, count:", bidPlacedEvent.bidCount);
+      expect(bidPlacedEvent.bidCount).to.equal(1);
+
+      // Queued bids only reach the encrypted state through process_bids.
+      const bidsProcessedPromise = awaitEvent("bidsProcessedEvent");
+      const processComputationOffset = new anchor.BN(randomBytes(8), "hex");
+      const processSig = await program.methods
+        .processBids(processComputationOffset)
+        .accountsPartial({
+          payer: owner.publicKey,
+          auction: auctionPDA,
           computationAccount: getComputationAccAddress(
             arciumEnv.arciumClusterOffset,
-            bidComputationOffset
+            processComputationOffset
           ),
           clusterAccount,
           mxeAccount: getMXEAccAddress(program.programId),
@@ -192,22 +638,28 @@ before(async () => {
           ),
           compDefAccount: getCompDefAccAddress(
             program.programId,
-            Buffer.from(getCompDefAccOffset("place_bid")).readUInt32LE()
+            Buffer.from(getCompDefAccOffset("process_bids")).readUInt32LE()
           ),
         })
+        .remainingAccounts(await batchReceipts(auctionPDA))
         .rpc({ skipPreflight: true, commitment: "confirmed" });
 
-      console.log("   Place bid tx:", placeBidSig);
-      const bidFinalizeSig = await awaitComputationFinalization(
+      console.log("   Process bids tx:", processSig);
+      const processFinalizeSig = await awaitComputationFinalization(
         provider as anchor.AnchorProvider,
-        bidComputationOffset,
+        processComputationOffset,
         program.programId,
         "confirmed"
       );
-      console.log("   Finalize tx:", bidFinalizeSig);
-      const bidPlacedEvent = await bidPlacedPromise;
-      console.log("   Bid placed, count:", bidPlacedEvent.bidCount);
-      expect(bidPlacedEvent.bidCount).to.equal(1);
+      console.log("   Finalize tx:", processFinalizeSig);
+      const bidsProcessedEvent = await bidsProcessedPromise;
+      expect(bidsProcessedEvent.processed).to.equal(1);
+      expect(bidsProcessedEvent.queued).to.equal(0);
+      const confirmedReceipt = await program.account.bidReceipt.fetch(
+        auctionPda("receipt", auctionPDA, bidder.publicKey)
+      );
+      expect(confirmedReceipt.confirmed).to.equal(true);
+      expect(confirmedReceipt.computationOffset.eq(processComputationOffset)).to.equal(true);
       console.log("\nStep 3: Closing auction...");
       const auctionClosedPromise = awaitEvent("auctionClosedEvent");
 
@@ -267,7 +719,7 @@ before(async () => {
       console.log(
         "   Payment amount:",
         auctionResolvedEvent.paymentAmount.toNumber(),
-        "lamports"
+        "quote tokens"
       );
 
       // Verify: In first-price, winner pays their bid (500)
@@ -280,10 +732,700 @@ before(async () => {
       );
       expect(actualWinner).to.equal(expectedWinner);
 
+      // The result is also kept on-chain for settlement and other programs.
+      const [allocationsPDA] = PublicKey.findProgramAddressSync(
+        [Buffer.from("allocations"), auctionPDA.toBuffer()],
+        program.programId
+      );
+      const allocations = await program.account.auctionAllocations.fetch(
+        allocationsPDA
+      );
+      expect(allocations.winner.toBase58()).to.equal(
+        bidder.publicKey.toBase58()
+      );
+      expect(allocations.clearingPrice.toNumber()).to.equal(500);
+      expect(allocations.resolvedAt.toNumber()).to.be.greaterThan(0);
+
+      console.log("\nStep 5: Fetching the bidder's private result...");
+      const bidderResultPromise = awaitEvent("bidderResultEvent");
+      const resultComputationOffset = new anchor.BN(randomBytes(8), "hex");
+      const resultNonce = randomBytes(16);
+      const resultSig = await program.methods
+        .bidderResult(
+          resultComputationOffset,
+          new anchor.BN(deserializeLE(resultNonce).toString())
+        )
+        .accountsPartial({
+          bidder: bidder.publicKey,
+          auction: auctionPDA,
+          computationAccount: getComputationAccAddress(
+            arciumEnv.arciumClusterOffset,
+            resultComputationOffset
+          ),
+          clusterAccount,
+          mxeAccount: getMXEAccAddress(program.programId),
+          mempoolAccount: getMempoolAccAddress(arciumEnv.arciumClusterOffset),
+          executingPool: getExecutingPoolAccAddress(
+            arciumEnv.arciumClusterOffset
+          ),
+          compDefAccount: getCompDefAccAddress(
+            program.programId,
+            Buffer.from(getCompDefAccOffset("bidder_result")).readUInt32LE()
+          ),
+        })
+        .rpc({ skipPreflight: true, commitment: "confirmed" });
+      console.log("   Bidder result tx:", resultSig);
+      await awaitComputationFinalization(
+        provider as anchor.AnchorProvider,
+        resultComputationOffset,
+        program.programId,
+        "confirmed"
+      );
+      const bidderResultEvent = await bidderResultPromise;
+      // [won, quantity, price, bid_amount, bid_quantity]
+      const bidderResult = cipher.decrypt(
+        bidderResultEvent.ciphertexts,
+        new Uint8Array(bidderResultEvent.nonce)
+      );
+      expect(bidderResult).to.deep.equal([
+        BigInt(1),
+        BigInt(1),
+        BigInt(500),
+        BigInt(500),
+        BigInt(1),
+      ]);
+
+      console.log("\nStep 6: Settling...");
+      const allocationClaimedPromise = awaitEvent("allocationClaimedEvent");
+      const claimSig = await program.methods
+        .claimAllocation(0)
+        .accountsPartial({
+          payer: owner.publicKey,
+          auction: auctionPDA,
+          saleMint,
+          recipientSaleAccount: ownerSaleAccount,
+          saleTokenProgram: TOKEN_PROGRAM_ID,
+        })
+        .rpc({ commitment: "confirmed" });
+      console.log("   Claim allocation tx:", claimSig);
+      const allocationClaimedEvent = await allocationClaimedPromise;
+      expect(allocationClaimedEvent.amount.toNumber()).to.equal(1_000);
+
+      const proceedsClaimedPromise = awaitEvent("proceedsClaimedEvent");
+      const proceedsSig = await program.methods
+        .claimProceeds()
+        .accountsPartial({
+          payer: owner.publicKey,
+          auction: auctionPDA,
+          saleMint,
+          quoteMint,
+          authoritySaleAccount: ownerSaleAccount,
+          authorityQuoteAccount: ownerQuoteAccount,
+          saleTokenProgram: TOKEN_PROGRAM_ID,
+          quoteTokenProgram: TOKEN_PROGRAM_ID,
+        })
+        .rpc({ commitment: "confirmed" });
+      console.log("   Claim proceeds tx:", proceedsSig);
+      const proceedsClaimedEvent = await proceedsClaimedPromise;
+      expect(proceedsClaimedEvent.paymentAmount.toNumber()).to.equal(500);
+      expect(proceedsClaimedEvent.unsoldAmount.toNumber()).to.equal(0);
+
+      const withdrawnPromise = awaitEvent("depositWithdrawnEvent");
+      const withdrawSig = await program.methods
+        .withdrawDeposit()
+        .accountsPartial({
+          bidder: bidder.publicKey,
+          auction: auctionPDA,
+          quoteMint,
+          bidderQuoteAccount: ownerQuoteAccount,
+          quoteTokenProgram: TOKEN_PROGRAM_ID,
+        })
+        .rpc({ commitment: "confirmed" });
+      console.log("   Withdraw deposit tx:", withdrawSig);
+      const withdrawnEvent = await withdrawnPromise;
+      // 1_000 deposited, 500 paid: the winner gets the excess back.
+      expect(withdrawnEvent.amount.toNumber()).to.equal(500);
+
+      const receiptClosedPromise = awaitEvent("bidReceiptClosedEvent");
+      const closeReceiptSig = await program.methods
+        .closeBidReceipt()
+        .accountsPartial({
+          bidder: bidder.publicKey,
+          auction: auctionPDA,
+        })
+        .rpc({ commitment: "confirmed" });
+      console.log("   Close bid receipt tx:", closeReceiptSig);
+      const receiptClosedEvent = await receiptClosedPromise;
+      expect(receiptClosedEvent.bidder.toBase58()).to.equal(
+        bidder.publicKey.toBase58()
+      );
+
       console.log("\n   First-price auction test PASSED!");
 
     });
   });
+  describe("Uniform Price Auction", () => {
+    it("fills the supply from the highest bid down at one clearing price", async () => {
+      const auction = await createAuction({
+        auctionType: "uniformPrice",
+        saleAmount: 1_000,
+        totalUnits: 10,
+      });
+      const [a, b, c] = [await newBidder(), await newBidder(), await newBidder()];
+      await placeBid(auction, a, { amount: 50, quantity: 3, deposit: 150 });
+      await placeBid(auction, b, { amount: 40, quantity: 4, deposit: 160 });
+      await placeBid(auction, c, { amount: 30, quantity: 5, deposit: 150 });
+      await drainBidQueue(auction);
+      await closeAuction(auction);
+
+      const resolvedPromise = awaitEvent("uniformPriceResolvedEvent");
+      const allocations = await resolveAuction(auction, "uniformPrice");
+      const resolved = await resolvedPromise;
+      expect(resolved.clearingPrice.toNumber()).to.equal(30);
+      expect(resolved.unitsSold.toNumber()).to.equal(10);
+
+      // Everyone pays the clearing price; the lowest bid takes what is left.
+      const filled = allocations.allocations.filter((x) => x.quantity.toNumber() > 0);
+      expect(filled.map((x) => x.bidder.toBase58())).to.deep.equal(
+        [a, b, c].map((x) => x.keypair.publicKey.toBase58())
+      );
+      expect(filled.map((x) => x.quantity.toNumber())).to.deep.equal([3, 4, 3]);
+      expect(filled.map((x) => x.payment.toNumber())).to.deep.equal([90, 120, 90]);
+    });
+
+    it("leaves supply unsold when ranked demand falls short", async () => {
+      const auction = await createAuction({
+        auctionType: "uniformPrice",
+        saleAmount: 1_000,
+        totalUnits: 10,
+      });
+      const [a, b] = [await newBidder(), await newBidder()];
+      await placeBid(auction, a, { amount: 50, quantity: 2, deposit: 100 });
+      await placeBid(auction, b, { amount: 40, quantity: 3, deposit: 120 });
+      await drainBidQueue(auction);
+      await closeAuction(auction);
+
+      const resolvedPromise = awaitEvent("uniformPriceResolvedEvent");
+      await resolveAuction(auction, "uniformPrice");
+      const resolved = await resolvedPromise;
+      // No lower bid exists, so the lowest filled bid sets the price.
+      expect(resolved.clearingPrice.toNumber()).to.equal(40);
+      expect(resolved.unitsSold.toNumber()).to.equal(5);
+
+      const proceedsPromise = awaitEvent("proceedsClaimedEvent");
+      await program.methods
+        .claimProceeds()
+        .accountsPartial({
+          payer: owner.publicKey,
+          auction,
+          saleMint,
+          quoteMint,
+          authoritySaleAccount: ownerSaleAccount,
+          authorityQuoteAccount: ownerQuoteAccount,
+          saleTokenProgram: TOKEN_PROGRAM_ID,
+          quoteTokenProgram: TOKEN_PROGRAM_ID,
+        })
+        .rpc({ commitment: "confirmed" });
+      const proceeds = await proceedsPromise;
+      expect(proceeds.paymentAmount.toNumber()).to.equal(200);
+      // Five of ten units, at 100 sale tokens each, go back to the authority.
+      expect(proceeds.unsoldAmount.toNumber()).to.equal(500);
+    });
+  });
+  describe("Discriminatory Auction", () => {
+    it("fills more than four winners, each at their own price", async () => {
+      const auction = await createAuction({
+        auctionType: "discriminatory",
+        saleAmount: 600,
+        totalUnits: 6,
+      });
+      const prices = [60, 55, 50, 45, 40, 35];
+      const bidders: Bidder[] = [];
+      for (const price of prices) {
+        const bidder = await newBidder();
+        await placeBid(auction, bidder, { amount: price, quantity: 1, deposit: price });
+        bidders.push(bidder);
+      }
+      await drainBidQueue(auction);
+      await closeAuction(auction);
+
+      const resolvedPromise = awaitEvent("discriminatoryResolvedEvent");
+      const allocations = await resolveAuction(auction, "discriminatory");
+      const resolved = await resolvedPromise;
+      expect(resolved.unitsSold.toNumber()).to.equal(6);
+      expect(resolved.totalPayment.toNumber()).to.equal(285);
+
+      const filled = allocations.allocations.filter((x) => x.quantity.toNumber() > 0);
+      expect(filled.map((x) => x.bidder.toBase58())).to.deep.equal(
+        bidders.map((x) => x.keypair.publicKey.toBase58())
+      );
+      expect(filled.map((x) => x.payment.toNumber())).to.deep.equal(prices);
+      // The lowest accepted price is recorded as the clearing price.
+      expect(allocations.clearingPrice.toNumber()).to.equal(35);
+    });
+  });
+  describe("SOL-Denominated Sales", () => {
+    it("wraps a native SOL deposit into the quote vault", async () => {
+      const auction = await createAuction({
+        auctionType: "firstPrice",
+        quoteMint: NATIVE_MINT,
+      });
+      const bidder = await newBidder();
+      const quoteVault = auctionPda("quote_vault", auction);
+      const before = await provider.connection.getTokenAccountBalance(quoteVault);
+      await placeBid(auction, bidder, { amount: 500, quantity: 1, deposit: 500, native: true });
+
+      const after = await provider.connection.getTokenAccountBalance(quoteVault);
+      expect(Number(after.value.amount) - Number(before.value.amount)).to.equal(500);
+      const receipt = await program.account.bidReceipt.fetch(
+        auctionPda("receipt", auction, bidder.keypair.publicKey)
+      );
+      expect(receipt.deposit.toNumber()).to.equal(500);
+    });
+
+    it("rejects a native deposit when the quote mint is not wrapped SOL", async () => {
+      const auction = await createAuction({ auctionType: "firstPrice" });
+      const bidder = await newBidder();
+      await expectError(
+        placeBid(auction, bidder, { amount: 500, quantity: 1, deposit: 500, native: true }),
+        "NativeDepositNotSupported"
+      );
+    });
+  });
+  describe("Bid Revision and Cancellation", () => {
+    it("replaces an earlier bid when revisions are allowed", async () => {
+      const auction = await createAuction({
+        auctionType: "uniformPrice",
+        saleAmount: 400,
+        totalUnits: 4,
+        allowBidRevision: true,
+      });
+      const bidder = await newBidder();
+      await placeBid(auction, bidder, { amount: 50, quantity: 1, deposit: 50 });
+      // A revision waits until the signer's earlier bid has left the queue.
+      await expectError(
+        placeBid(auction, bidder, { amount: 40, quantity: 3, deposit: 70 }),
+        "BidAlreadyQueued"
+      );
+      await drainBidQueue(auction);
+      await placeBid(auction, bidder, { amount: 40, quantity: 3, deposit: 70 });
+      await drainBidQueue(auction);
+      await closeAuction(auction);
+
+      const allocations = await resolveAuction(auction, "uniformPrice");
+      const filled = allocations.allocations.filter((x) => x.quantity.toNumber() > 0);
+      // Only the revised bid is ranked, backed by both deposits.
+      expect(filled.length).to.equal(1);
+      expect(filled[0].quantity.toNumber()).to.equal(3);
+      expect(allocations.clearingPrice.toNumber()).to.equal(40);
+    });
+
+    it("rejects a second bid when revisions are not allowed", async () => {
+      const auction = await createAuction({ auctionType: "uniformPrice", totalUnits: 4 });
+      const bidder = await newBidder();
+      await placeBid(auction, bidder, { amount: 50, quantity: 1, deposit: 50 });
+      await expectError(
+        placeBid(auction, bidder, { amount: 60, quantity: 1, deposit: 60 }),
+        "DuplicateBid"
+      );
+    });
+
+    it("restores the ranking when a bid is cancelled", async () => {
+      const auction = await createAuction({
+        auctionType: "uniformPrice",
+        saleAmount: 400,
+        totalUnits: 4,
+      });
+      const [a, b] = [await newBidder(), await newBidder()];
+      await placeBid(auction, a, { amount: 50, quantity: 2, deposit: 100 });
+      await placeBid(auction, b, { amount: 40, quantity: 2, deposit: 80 });
+      await drainBidQueue(auction);
+
+      const cancelledPromise = awaitEvent("bidCancelledEvent");
+      await cancelBid(auction, a);
+      const cancelled = await cancelledPromise;
+      expect(cancelled.bidder.toBase58()).to.equal(a.keypair.publicKey.toBase58());
+      expect(cancelled.bidCount).to.equal(1);
+      const receipt = await program.account.bidReceipt.fetch(
+        auctionPda("receipt", auction, a.keypair.publicKey)
+      );
+      expect(receipt.active).to.equal(false);
+
+      await closeAuction(auction);
+      const allocations = await resolveAuction(auction, "uniformPrice");
+      const filled = allocations.allocations.filter((x) => x.quantity.toNumber() > 0);
+      expect(filled.map((x) => x.bidder.toBase58())).to.deep.equal([
+        b.keypair.publicKey.toBase58(),
+      ]);
+      expect(allocations.clearingPrice.toNumber()).to.equal(40);
+    });
+
+    it("rejects cancelling a receipt with no active bid", async () => {
+      const auction = await createAuction({ auctionType: "uniformPrice", totalUnits: 4 });
+      const bidder = await newBidder();
+      await placeBid(auction, bidder, { amount: 50, quantity: 1, deposit: 50 });
+      await drainBidQueue(auction);
+      await cancelBid(auction, bidder);
+
+      const before = await program.account.auction.fetch(auction);
+      await expectError(cancelBid(auction, bidder), "NoActiveBid");
+      const after = await program.account.auction.fetch(auction);
+      // Nothing was queued for the rejected cancellation.
+      expect(after.bidsInFlight).to.equal(before.bidsInFlight);
+    });
+
+    it("only expires a cancellation after the timeout", async () => {
+      const auction = await createAuction({ auctionType: "uniformPrice", totalUnits: 4 });
+      const bidder = await newBidder();
+      await placeBid(auction, bidder, { amount: 50, quantity: 1, deposit: 50 });
+      await drainBidQueue(auction);
+      const receipt = auctionPda("receipt", auction, bidder.keypair.publicKey);
+      const expire = () =>
+        program.methods
+          .expireCancelBid()
+          .accountsPartial({ auction, receipt })
+          .rpc({ commitment: "confirmed" });
+      await expectError(expire(), "NothingToRetry");
+
+      const computationOffset = new anchor.BN(randomBytes(8), "hex");
+      await program.methods
+        .cancelBid(computationOffset)
+        .accountsPartial({
+          bidder: bidder.keypair.publicKey,
+          auction,
+          ...arciumAccounts(computationOffset, "cancel_bid"),
+        })
+        .signers([bidder.keypair])
+        .rpc({ commitment: "confirmed" });
+      await expectError(expire(), "ComputationNotExpired");
+      await finalize(computationOffset);
+    });
+
+    it("brings back a bid pushed out of the ranking after a cancellation", async () => {
+      const auction = await createAuction({
+        auctionType: "uniformPrice",
+        saleAmount: 1_600,
+        totalUnits: 16,
+        maxBids: 20,
+      });
+      // One more bidder than the ranking holds: the lowest is pushed out.
+      const bidders: Bidder[] = [];
+      for (let price = 100; price <= 116; price++) {
+        const bidder = await newBidder();
+        await placeBid(auction, bidder, { amount: price, quantity: 1, deposit: price });
+        bidders.push(bidder);
+      }
+      await drainBidQueue(auction);
+      await cancelBid(auction, bidders[16]);
+      await closeAuction(auction);
+      expect((await program.account.auction.fetch(auction)).rerankRequired).to.equal(true);
+      await expectError(
+        queueResolution(auction, "uniformPrice", new anchor.BN(randomBytes(8), "hex")),
+        "RerankRequired"
+      );
+
+      await rerankBids(auction);
+      const allocations = await resolveAuction(auction, "uniformPrice");
+      const filled = allocations.allocations.filter((x) => x.quantity.toNumber() > 0);
+      expect(filled.length).to.equal(16);
+      expect(filled.some((x) => x.bidder.equals(bidders[0].keypair.publicKey))).to.equal(true);
+      expect(allocations.clearingPrice.toNumber()).to.equal(100);
+    });
+
+    it("rejects a bid cap of zero", async () => {
+      await expectError(
+        createAuction({ auctionType: "uniformPrice", totalUnits: 4, maxBids: 0 }),
+        "InvalidMaxBids"
+      );
+    });
+  });
+  describe("Batched Bid Processing", () => {
+    it("drains more bids than one batch holds across repeated cranks", async () => {
+      const auction = await createAuction({ auctionType: "uniformPrice", totalUnits: 6 });
+      const bidders: Bidder[] = [];
+      for (let i = 0; i < BID_BATCH_SIZE + 2; i++) {
+        const bidder = await newBidder();
+        await placeBid(auction, bidder, { amount: 20 + i, quantity: 1, deposit: 20 + i });
+        bidders.push(bidder);
+      }
+      expect((await program.account.auction.fetch(auction)).bidsInFlight).to.equal(6);
+
+      const first = await processBids(auction);
+      expect(first.processed).to.equal(BID_BATCH_SIZE);
+      expect(first.queued).to.equal(2);
+      const second = await processBids(auction);
+      expect(second.processed).to.equal(2);
+      expect(second.queued).to.equal(0);
+
+      expect((await program.account.auction.fetch(auction)).bidsInFlight).to.equal(0);
+      for (const bidder of bidders) {
+        const receipt = await program.account.bidReceipt.fetch(
+          auctionPda("receipt", auction, bidder.keypair.publicKey)
+        );
+        expect(receipt.confirmed).to.equal(true);
+      }
+      await expectError(processBids(auction), "BidQueueEmpty");
+    });
+
+    it("only expires a batch after the timeout", async () => {
+      const auction = await createAuction({ auctionType: "uniformPrice", totalUnits: 1 });
+      const expire = () =>
+        program.methods
+          .expireBidBatch()
+          .accountsPartial({ auction })
+          .rpc({ commitment: "confirmed" });
+      await expectError(expire(), "NoBatchInFlight");
+
+      const bidder = await newBidder();
+      await placeBid(auction, bidder, { amount: 20, quantity: 1, deposit: 20 });
+      const computationOffset = new anchor.BN(randomBytes(8), "hex");
+      await program.methods
+        .processBids(computationOffset)
+        .accountsPartial({
+          payer: owner.publicKey,
+          auction,
+          ...arciumAccounts(computationOffset, "process_bids"),
+        })
+        .remainingAccounts(await batchReceipts(auction))
+        .rpc({ commitment: "confirmed" });
+      await expectError(expire(), "ComputationNotExpired");
+      await finalize(computationOffset);
+    });
+
+    it("rejects receipts that do not match the batch", async () => {
+      const auction = await createAuction({ auctionType: "uniformPrice", totalUnits: 1 });
+      const [a, b] = [await newBidder(), await newBidder()];
+      await placeBid(auction, a, { amount: 20, quantity: 1, deposit: 20 });
+      const computationOffset = new anchor.BN(randomBytes(8), "hex");
+      await expectError(
+        program.methods
+          .processBids(computationOffset)
+          .accountsPartial({
+            payer: owner.publicKey,
+            auction,
+            ...arciumAccounts(computationOffset, "process_bids"),
+          })
+          .remainingAccounts([
+            {
+              pubkey: auctionPda("receipt", auction, b.keypair.publicKey),
+              isSigner: false,
+              isWritable: true,
+            },
+          ])
+          .rpc({ commitment: "confirmed" }),
+        "InvalidBidReceipts"
+      );
+    });
+  });
+  describe("Auction Rules", () => {
+    async function resolveUniform(auction: PublicKey) {
+      await drainBidQueue(auction);
+      await closeAuction(auction);
+      const resolvedPromise = awaitEvent("uniformPriceResolvedEvent");
+      const allocations = await resolveAuction(auction, "uniformPrice");
+      return { allocations, resolved: await resolvedPromise };
+    }
+
+    function quantityOf(
+      allocations: { allocations: { bidder: PublicKey; quantity: anchor.BN }[] },
+      bidder: Bidder
+    ) {
+      return allocations.allocations
+        .filter((x) => x.bidder.equals(bidder.keypair.publicKey))
+        .reduce((total, x) => total + x.quantity.toNumber(), 0);
+    }
+
+    it("gives a tie at the margin to the earliest bid", async () => {
+      const auction = await createAuction({ auctionType: "uniformPrice", totalUnits: 4 });
+      const [a, b] = [await newBidder(), await newBidder()];
+      await placeBid(auction, a, { amount: 10, quantity: 4, deposit: 40 });
+      await placeBid(auction, b, { amount: 10, quantity: 4, deposit: 40 });
+      const { allocations, resolved } = await resolveUniform(auction);
+      expect(quantityOf(allocations, a)).to.equal(4);
+      expect(quantityOf(allocations, b)).to.equal(0);
+      expect(resolved.priceShared).to.equal(true);
+    });
+
+    it("splits a tie at the margin pro rata", async () => {
+      const auction = await createAuction({
+        auctionType: "uniformPrice",
+        totalUnits: 4,
+        tieBreak: "proRata",
+      });
+      const [a, b] = [await newBidder(), await newBidder()];
+      await placeBid(auction, a, { amount: 10, quantity: 4, deposit: 40 });
+      await placeBid(auction, b, { amount: 10, quantity: 4, deposit: 40 });
+      const { allocations, resolved } = await resolveUniform(auction);
+      expect(quantityOf(allocations, a)).to.equal(2);
+      expect(quantityOf(allocations, b)).to.equal(2);
+      expect(resolved.priceShared).to.equal(true);
+    });
+
+    it("rejects bids past max_bids", async () => {
+      const auction = await createAuction({
+        auctionType: "uniformPrice",
+        totalUnits: 4,
+        maxBids: 1,
+      });
+      const [a, b] = [await newBidder(), await newBidder()];
+      await placeBid(auction, a, { amount: 10, quantity: 1, deposit: 10 });
+      await expectError(
+        placeBid(auction, b, { amount: 10, quantity: 1, deposit: 10 }),
+        "MaxBidsReached"
+      );
+    });
+
+    it("admits only allowlisted bidders, up to their cap", async () => {
+      const [a, b, outsider] = [await newBidder(), await newBidder(), await newBidder()];
+      const leaf = (bidder: Bidder, maxAllocation: number) => {
+        const cap = Buffer.alloc(8);
+        cap.writeBigUInt64LE(BigInt(maxAllocation));
+        return createHash("sha256")
+          .update(Buffer.concat([bidder.keypair.publicKey.toBuffer(), cap]))
+          .digest();
+      };
+      const [leafA, leafB] = [leaf(a, 2), leaf(b, 0)];
+      const root = createHash("sha256")
+        .update(Buffer.concat(Buffer.compare(leafA, leafB) <= 0 ? [leafA, leafB] : [leafB, leafA]))
+        .digest();
+
+      const auction = await createAuction({
+        auctionType: "uniformPrice",
+        totalUnits: 6,
+        allowlistRoot: Array.from(root),
+      });
+      await expectError(
+        placeBid(auction, outsider, {
+          amount: 10,
+          quantity: 1,
+          deposit: 10,
+          maxAllocation: 2,
+          proof: [Array.from(leafB)],
+        }),
+        "NotAllowlisted"
+      );
+      // A asks for 5 units but the allowlist caps them at 2; B has no cap.
+      await placeBid(auction, a, {
+        amount: 20,
+        quantity: 5,
+        deposit: 100,
+        maxAllocation: 2,
+        proof: [Array.from(leafB)],
+      });
+      await placeBid(auction, b, {
+        amount: 10,
+        quantity: 4,
+        deposit: 40,
+        proof: [Array.from(leafA)],
+      });
+      const { allocations } = await resolveUniform(auction);
+      expect(quantityOf(allocations, a)).to.equal(2);
+      expect(quantityOf(allocations, b)).to.equal(4);
+    });
+
+    it("trims bids to the auction's max spend", async () => {
+      const auction = await createAuction({
+        auctionType: "uniformPrice",
+        totalUnits: 10,
+        maxBidSpend: 100,
+      });
+      const bidder = await newBidder();
+      // 100 buys three whole units at 30.
+      await placeBid(auction, bidder, { amount: 30, quantity: 5, deposit: 150 });
+      const { allocations } = await resolveUniform(auction);
+      expect(quantityOf(allocations, bidder)).to.equal(3);
+    });
+
+    it("refunds every deposit in full when the auction is cancelled", async () => {
+      const auction = await createAuction({ auctionType: "uniformPrice", totalUnits: 4 });
+      const bidder = await newBidder();
+      await placeBid(auction, bidder, { amount: 20, quantity: 2, deposit: 75 });
+      await program.methods
+        .cancelAuction()
+        .accountsPartial({ authority: owner.publicKey, auction })
+        .rpc({ commitment: "confirmed" });
+
+      const withdrawnPromise = awaitEvent("depositWithdrawnEvent");
+      await program.methods
+        .withdrawDeposit()
+        .accountsPartial({
+          bidder: bidder.keypair.publicKey,
+          auction,
+          quoteMint,
+          bidderQuoteAccount: bidder.quoteAccount,
+          quoteTokenProgram: TOKEN_PROGRAM_ID,
+        })
+        .signers([bidder.keypair])
+        .rpc({ commitment: "confirmed" });
+      expect((await withdrawnPromise).amount.toNumber()).to.equal(75);
+    });
+
+    it("has nothing to retry while the auction is healthy", async () => {
+      const auction = await createAuction({ auctionType: "uniformPrice", totalUnits: 4 });
+      const computationOffset = new anchor.BN(randomBytes(8), "hex");
+      await expectError(
+        program.methods
+          .retryInitAuctionState(
+            computationOffset,
+            new anchor.BN(deserializeLE(randomBytes(16)).toString())
+          )
+          .accountsPartial({
+            authority: owner.publicKey,
+            auction,
+            ...arciumAccounts(computationOffset, "init_auction_state"),
+          })
+          .rpc({ commitment: "confirmed" }),
+        "NothingToRetry"
+      );
+    });
+
+    it("holds resolution until queued bids are processed", async () => {
+      const auction = await createAuction({ auctionType: "uniformPrice", totalUnits: 4 });
+      const bidder = await newBidder();
+      await placeBid(auction, bidder, { amount: 20, quantity: 2, deposit: 40 });
+      await closeAuction(auction);
+      await expectError(
+        queueResolution(auction, "uniformPrice", new anchor.BN(randomBytes(8), "hex")),
+        "BidsInFlight"
+      );
+      // The queue still drains after closing, and then resolution goes through.
+      await drainBidQueue(auction);
+      const allocations = await resolveAuction(auction, "uniformPrice");
+      expect(quantityOf(allocations, bidder)).to.equal(2);
+    });
+
+    it("discards a cancellation that lands after bidding closed", async () => {
+      const auction = await createAuction({ auctionType: "uniformPrice", totalUnits: 4 });
+      const bidder = await newBidder();
+      await placeBid(auction, bidder, { amount: 20, quantity: 2, deposit: 40 });
+      await drainBidQueue(auction);
+
+      const stalePromise = awaitEvent("staleComputationEvent");
+      const computationOffset = new anchor.BN(randomBytes(8), "hex");
+      await program.methods
+        .cancelBid(computationOffset)
+        .accountsPartial({
+          bidder: bidder.keypair.publicKey,
+          auction,
+          ...arciumAccounts(computationOffset, "cancel_bid"),
+        })
+        .signers([bidder.keypair])
+        .rpc({ commitment: "confirmed" });
+      // Close before the MPC callback lands.
+      await closeAuction(auction);
+      await finalize(computationOffset);
+      const stale = await stalePromise;
+      expect(stale.bidder?.toBase58()).to.equal(bidder.keypair.publicKey.toBase58());
+
+      const receipt = await program.account.bidReceipt.fetch(
+        auctionPda("receipt", auction, bidder.keypair.publicKey)
+      );
+      expect(receipt.active).to.equal(true);
+      expect(receipt.pending).to.equal(false);
+      const allocations = await resolveAuction(auction, "uniformPrice");
+      expect(quantityOf(allocations, bidder)).to.equal(2);
+    });
+  });
   async function initCompDef(
     program: Program<WsConfidentialPrimaryMarketAuction>,
     owner: anchor.web3.Keypair,
@@ -310,9 +1452,9 @@ before(async () => {
           .signers([owner])
           .rpc({ preflightCommitment: "confirmed" });
         break;
-      case "place_bid":
+      case "process_bids":
         tx = await program.methods
-          .initPlaceBidCompDef()
+          .initProcessBidsCompDef()
           .accounts({
             compDefAccount: compDefPDA,
             payer: owner.publicKey,
@@ -343,6 +1485,50 @@ before(async () => {
           .signers([owner])
           .rpc({ preflightCommitment: "confirmed" });
         break;
+      case "uniform_price_winners":
+        tx = await program.methods
+          .initUniformPriceWinnersCompDef()
+          .accounts({
+            compDefAccount: compDefPDA,
+            payer: owner.publicKey,
+            mxeAccount: getMXEAccAddress(program.programId),
+          })
+          .signers([owner])
+          .rpc({ preflightCommitment: "confirmed" });
+        break;
+      case "discriminatory_winners":
+        tx = await program.methods
+          .initDiscriminatoryWinnersCompDef()
+          .accounts({
+            compDefAccount: compDefPDA,
+            payer: owner.publicKey,
+            mxeAccount: getMXEAccAddress(program.programId),
+          })
+          .signers([owner])
+          .rpc({ preflightCommitment: "confirmed" });
+        break;
+      case "bidder_result":
+        tx = await program.methods
+          .initBidderResultCompDef()
+          .accounts({
+            compDefAccount: compDefPDA,
+            payer: owner.publicKey,
+            mxeAccount: getMXEAccAddress(program.programId),
+          })
+          .signers([owner])
+          .rpc({ preflightCommitment: "confirmed" });
+        break;
+      case "cancel_bid":
+        tx = await program.methods
+          .initCancelBidCompDef()
+          .accounts({
+            compDefAccount: compDefPDA,
+            payer: owner.publicKey,
+            mxeAccount: getMXEAccAddress(program.programId),
+          })
+          .signers([owner])
+          .rpc({ preflightCommitment: "confirmed" });
+        break;
       default:
         throw new Error(`Unknown circuit: ${circuitName}`);
     }
@@ -363,19 +1549,6 @@ before(async () => {
     return tx;
   }
 });
-function splitPubkeyToU128s(pubkey: Uint8Array): { lo: bigint; hi: bigint } {
-  // Lower 128 bits (first 16 bytes)
-  const loBytes = pubkey.slice(0, 16);
-  // Upper 128 bits (last 16 bytes)
-  const hiBytes = pubkey.slice(16, 32);
-
-  // Convert to bigint (little-endian)
-  const lo = deserializeLE(loBytes);
-  const hi = deserializeLE(hiBytes);
-
-  return { lo, hi };
-}
-
 async function getMXEPublicKeyWithRetry(
   provider: anchor.AnchorProvider,
   programId: PublicKey,